@@ -0,0 +1,284 @@
+//! Card metadata, read from a `.json` or `.toml` file next to each image
+//! (`<set>/<card id>.json` next to `<set>/<card id>.jpg`):
+//!
+//! ```json
+//! {
+//!     "name": "Flareon ex",
+//!     "supertype": "Pokémon",
+//!     "subtypes": ["ex"],
+//!     "hp": 100,
+//!     "types": ["Fire"],
+//!     "stage": "Stage 1",
+//!     "evolves_from": "Eevee",
+//!     "set": "DS",
+//!     "number": "108"
+//! }
+//! ```
+//!
+//! Only `name` and `supertype` are required. Cards without a metadata file
+//! just don't have an entry.
+
+use serde::Deserialize;
+use std::fmt;
+use std::fs::read_to_string;
+use std::path::Path;
+
+use crate::config::CardLibrary;
+use crate::deck_list::Section;
+use crate::load_cards::CardIndexer;
+use crate::state::Card;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub enum Supertype
+{
+    Pokemon,
+    Trainer,
+    Energy,
+}
+
+impl TryFrom<String> for Supertype
+{
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error>
+    {
+        match s.to_lowercase().as_str() {
+            "pokémon" | "pokemon" => Ok(Supertype::Pokemon),
+            "trainer" => Ok(Supertype::Trainer),
+            "energy" => Ok(Supertype::Energy),
+            _ => Err(format!("unknown supertype {:?}", s)),
+        }
+    }
+}
+
+impl From<Supertype> for Section
+{
+    fn from(supertype: Supertype) -> Self
+    {
+        match supertype {
+            Supertype::Pokemon => Section::Pokemon,
+            Supertype::Trainer => Section::Trainer,
+            Supertype::Energy => Section::Energy,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(from = "String")]
+pub enum Stage
+{
+    Basic,
+    Stage1,
+    Stage2,
+    // Baby, Level-Up, BREAK, VMAX, etc. These don't need special handling yet
+    Other(String),
+}
+
+impl From<String> for Stage
+{
+    fn from(s: String) -> Self
+    {
+        let normalized: String =
+            s.to_lowercase().chars().filter(|c| c.is_ascii_alphanumeric()).collect();
+        match normalized.as_str() {
+            "basic" => Stage::Basic,
+            "stage1" => Stage::Stage1,
+            "stage2" => Stage::Stage2,
+            _ => Stage::Other(s),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct CardInfo
+{
+    pub name: String,
+    pub supertype: Supertype,
+    #[serde(default)]
+    pub subtypes: Vec<String>,
+    pub hp: Option<u32>,
+    #[serde(default)]
+    pub types: Vec<String>,
+    pub stage: Option<Stage>,
+    pub evolves_from: Option<String>,
+    pub set: Option<String>,
+    pub number: Option<String>,
+    pub regulation_mark: Option<String>,
+}
+
+impl CardInfo
+{
+    pub fn has_subtype(&self, subtype: &str) -> bool
+    {
+        self.subtypes.iter().any(|s| s.eq_ignore_ascii_case(subtype))
+    }
+
+    pub fn is_basic_pokemon(&self) -> bool
+    {
+        self.supertype == Supertype::Pokemon && self.stage == Some(Stage::Basic)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CardDbError
+{
+    pub path: String,
+    pub reason: String,
+}
+
+impl fmt::Display for CardDbError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "{}: {}", self.path, self.reason)
+    }
+}
+
+/// Metadata for every card the `CardIndexer` knows, indexed the same way.
+#[derive(Clone, Debug, Default)]
+pub struct CardDb
+{
+    infos: Vec<Option<CardInfo>>,
+}
+
+fn read_card_info(image_path: &Path) -> Result<Option<CardInfo>, CardDbError>
+{
+    let error =
+        |path: &Path, reason: String| CardDbError { path: path.display().to_string(), reason };
+
+    let json_path = image_path.with_extension("json");
+    if json_path.exists() {
+        let contents = read_to_string(&json_path).map_err(|e| error(&json_path, e.to_string()))?;
+        let info = serde_json::from_str(&contents).map_err(|e| error(&json_path, e.to_string()))?;
+        return Ok(Some(info));
+    }
+
+    let toml_path = image_path.with_extension("toml");
+    if toml_path.exists() {
+        let contents = read_to_string(&toml_path).map_err(|e| error(&toml_path, e.to_string()))?;
+        let info = toml::from_str(&contents).map_err(|e| error(&toml_path, e.to_string()))?;
+        return Ok(Some(info));
+    }
+
+    Ok(None)
+}
+
+impl CardDb
+{
+    /// Bad metadata files are reported but don't stop the rest from loading.
+    pub fn load(library: &CardLibrary, indexer: &CardIndexer) -> (Self, Vec<CardDbError>)
+    {
+        let mut db = CardDb::default();
+        let mut errors = Vec::new();
+        for card in 0..indexer.len() {
+            if let Err(e) = db.load_card(library, indexer, card) {
+                errors.push(e);
+            }
+        }
+        (db, errors)
+    }
+
+    /// (Re)reads the metadata for one card.
+    pub fn load_card(
+        &mut self,
+        library: &CardLibrary,
+        indexer: &CardIndexer,
+        card: Card,
+    ) -> Result<(), CardDbError>
+    {
+        if self.infos.len() <= card {
+            self.infos.resize(card + 1, None);
+        }
+        let (set, card_id) = indexer.identity(card);
+        let info = read_card_info(&library.image_path(set, card_id))?;
+        self.infos[card] = info;
+        Ok(())
+    }
+
+    /// Metadata without a file to read it from, for tests.
+    #[cfg(test)]
+    pub(crate) fn insert(&mut self, card: Card, info: CardInfo)
+    {
+        if self.infos.len() <= card {
+            self.infos.resize(card + 1, None);
+        }
+        self.infos[card] = Some(info);
+    }
+
+    pub fn get(&self, card: Card) -> Option<&CardInfo>
+    {
+        self.infos.get(card).and_then(|info| info.as_ref())
+    }
+
+    pub fn name(&self, card: Card) -> Option<&str>
+    {
+        self.get(card).map(|info| info.name.as_str())
+    }
+
+    pub fn supertype(&self, card: Card) -> Option<Supertype>
+    {
+        self.get(card).map(|info| info.supertype)
+    }
+
+    /// Every card with this name (ignoring case), e.g. all the prints of a card.
+    pub fn find_by_name(&self, name: &str) -> Vec<Card>
+    {
+        (0..self.infos.len())
+            .filter(|card| match self.name(*card) {
+                Some(card_name) => card_name.eq_ignore_ascii_case(name),
+                None => false,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn supertype(s: &str) -> Result<Supertype, String>
+    {
+        Supertype::try_from(s.to_string())
+    }
+
+    #[test]
+    fn supertypes_ignore_case_and_the_accent()
+    {
+        assert_eq!(supertype("Pokémon"), Ok(Supertype::Pokemon));
+        assert_eq!(supertype("POKEMON"), Ok(Supertype::Pokemon));
+        assert_eq!(supertype("trainer"), Ok(Supertype::Trainer));
+        assert_eq!(supertype("Energy"), Ok(Supertype::Energy));
+        assert!(supertype("Stadium").is_err());
+    }
+
+    #[test]
+    fn stages_ignore_case_spaces_and_dashes()
+    {
+        assert_eq!(Stage::from("Basic".to_string()), Stage::Basic);
+        assert_eq!(Stage::from("Stage 1".to_string()), Stage::Stage1);
+        assert_eq!(Stage::from("stage-2".to_string()), Stage::Stage2);
+        assert_eq!(Stage::from("VMAX".to_string()), Stage::Other("VMAX".to_string()));
+    }
+
+    #[test]
+    fn metadata_from_json_and_toml()
+    {
+        let json: CardInfo = serde_json::from_str(
+            r#"{"name": "Eevee", "supertype": "Pokémon", "stage": "Basic", "hp": 60}"#,
+        )
+        .unwrap();
+        assert!(json.is_basic_pokemon());
+        assert_eq!(json.hp, Some(60));
+
+        let toml: CardInfo =
+            toml::from_str("name = \"Flareon ex\"\nsupertype = \"Pokemon\"\nstage = \"Stage 1\"\n")
+                .unwrap();
+        assert!(!toml.is_basic_pokemon());
+        assert_eq!(toml.stage, Some(Stage::Stage1));
+
+        let unknown = serde_json::from_str::<CardInfo>(r#"{"name": "X", "supertype": "Item"}"#);
+        assert!(unknown.unwrap_err().to_string().contains("unknown supertype"));
+    }
+}
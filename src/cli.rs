@@ -0,0 +1,358 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::display_constants::{WINDOW_HEIGHT, WINDOW_WIDTH};
+use crate::history::DEFAULT_UNDO_DEPTH;
+use crate::net::DEFAULT_PORT;
+use crate::spectate::SpectatorView;
+use crate::state::Player;
+
+pub const USAGE: &str = "\
+Usage: tcgclient [play] [OPTIONS]
+       tcgclient validate <DECK> [--format <NAME>] [--card-root <DIR>]
+       tcgclient list-decks [--card-root <DIR>]
+       tcgclient replay <FILE> [--width <PIXELS>] [--height <PIXELS>]
+       tcgclient host [--port <PORT>] [OPTIONS]
+       tcgclient join <ADDRESS> [--port <PORT>] [--game <ID>] [--record <FILE>]
+                      [--width <PIXELS>] [--height <PIXELS>]
+       tcgclient watch [--view <VIEW>] [--delay <SECONDS>] [--spectate-port <PORT>]
+       tcgclient help
+
+Decks are paths relative to the card root's decks/ directory (like
+2009/amu_long.txt), or any other path to a deck file.
+
+Every game is recorded to the card root's replays/ directory when it ends.
+In a replay, ; and ' (or the arrow keys) step back and forward, Page Up/Down
+go by turn, and typing a number then Enter jumps to that turn.
+
+C starts a chat message, which Enter sends and Escape drops. F6-F8 and
+F10-F12 send quick announcements, like \"Using an Ability\" or \"Your turn\".
+
+host starts a game over the network and waits for someone to join (on
+localhost too: run join 127.0.0.1 in another window). The host picks both
+decks and plays the first player's side. Undo, loading and switching sides
+are off in network games. The host records the whole game, the joining
+player only what they saw of it. If the connection drops, the game pauses
+while the joining player reconnects; after restarting, join with --game and
+the game ID printed when joining to carry on.
+
+play and host take --spectate-port to let spectators on the same machine run
+watch. --view picks what they see: neutral (no hands), 1 or 2 (that player's
+view) or coach (both hands and all prizes); --delay holds it back. The game's
+--spectate-delay sets how far behind views showing hidden cards must be: the
+coach's view, and either player's view (only the joining player's when
+hosting).
+
+Options:
+  --card-root <DIR>       Card library to use (overrides $TCGCLIENT_CARD_ROOT
+                          and the config file)
+  --deck1 <DECK>          Player 1's deck (otherwise chosen on screen)
+  --deck2 <DECK>          Player 2's deck (otherwise chosen on screen)
+  --format <NAME>         Format to check decks against, from formats/<NAME>.toml
+  --width <PIXELS>        Window width
+  --height <PIXELS>       Window height
+  --seed <NUMBER>         Seed for shuffles and dice, to replay a game
+  --first-player <1|2>    Which player's side to start on
+  --load <FILE>           Carry on from a saved game instead of picking decks
+  --save-file <FILE>      Where F5 saves and F9 loads (default: the newest
+                          save in the card root's saves/ directory)
+  --record <FILE>         Where to write this game's recording
+  --undo-depth <N>        How many changes Z can undo (default 200, 0 for none)
+  --port <PORT>           Port to host on, or to join if the address has none
+                          (default 7777)
+  --game <ID>             Network game to rejoin
+  --spectate-port <PORT>  Port for spectators to watch on (default 7778 for
+                          watch, off otherwise)
+  --spectate-delay <SECONDS>
+                          Least --delay for views showing hidden cards
+                          (default 0, or 60 when hosting)
+  --view <VIEW>           What watch shows: neutral, 1, 2 or coach (default
+                          neutral)
+  --delay <SECONDS>       How far behind the game watch runs (default 0)
+  -h, --help              Show this message";
+
+#[derive(Clone, Debug)]
+pub struct PlayOptions
+{
+    pub deck1: Option<String>,
+    pub deck2: Option<String>,
+    pub format: Option<String>,
+    pub window_width: u32,
+    pub window_height: u32,
+    pub seed: Option<u64>,
+    pub first_player: Player,
+    pub undo_depth: usize,
+    pub load: Option<PathBuf>,
+    pub save_file: Option<PathBuf>,
+    pub record: Option<PathBuf>,
+    pub port: u16,
+    pub spectate_port: Option<u16>,
+    /// In seconds. None for the default, which depends on the command.
+    pub spectate_delay: Option<u64>,
+    pub game_id: Option<String>,
+}
+
+impl Default for PlayOptions
+{
+    fn default() -> Self
+    {
+        PlayOptions {
+            deck1: None,
+            deck2: None,
+            format: None,
+            window_width: WINDOW_WIDTH,
+            window_height: WINDOW_HEIGHT,
+            seed: None,
+            first_player: Player::Player1,
+            undo_depth: DEFAULT_UNDO_DEPTH,
+            load: None,
+            save_file: None,
+            record: None,
+            port: DEFAULT_PORT,
+            spectate_port: None,
+            spectate_delay: None,
+            game_id: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Command
+{
+    Play(PlayOptions),
+    Validate
+    {
+        deck: String, format: Option<String>
+    },
+    ListDecks,
+    /// Only the window size options are used.
+    Replay
+    {
+        file: PathBuf, options: PlayOptions
+    },
+    /// Play over the network, waiting for someone to join.
+    Host(PlayOptions),
+    /// Only the window size, port and game options are used.
+    Join
+    {
+        address: String, options: PlayOptions
+    },
+    /// Watch a game on this machine. Only the window size and spectate port
+    /// options are used.
+    Watch
+    {
+        view: SpectatorView, delay: Duration, options: PlayOptions
+    },
+    Help,
+}
+
+#[derive(Clone, Debug)]
+pub struct Cli
+{
+    pub card_root: Option<PathBuf>,
+    pub command: Command,
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String>
+{
+    value.parse().map_err(|_| format!("{} expects a number, got {:?}", flag, value))
+}
+
+impl Cli
+{
+    /// `args` shouldn't include the program name.
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, String>
+    {
+        let mut args = args.into_iter().peekable();
+        let subcommand = match args.peek().map(|s| s.as_str()) {
+            Some(
+                "play" | "validate" | "list-decks" | "replay" | "host" | "join" | "watch" | "help",
+            ) => args.next(),
+            _ => None,
+        };
+
+        let mut card_root = None;
+        let mut play = PlayOptions::default();
+        let mut positional = Vec::new();
+        let mut view = SpectatorView::Neutral;
+        let mut delay = Duration::ZERO;
+        let mut help = subcommand.as_deref() == Some("help");
+
+        while let Some(arg) = args.next() {
+            // Allow --flag=value as well as --flag value
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => {
+                    (flag.to_string(), Some(value.to_string()))
+                },
+                _ => (arg.clone(), None),
+            };
+            let mut value = || match inline_value.clone().or_else(|| args.next()) {
+                Some(value) => Ok(value),
+                None => Err(format!("{} needs a value", flag)),
+            };
+
+            match flag.as_str() {
+                "-h" | "--help" => help = true,
+                "--card-root" => card_root = Some(PathBuf::from(value()?)),
+                "--deck1" => play.deck1 = Some(value()?),
+                "--deck2" => play.deck2 = Some(value()?),
+                "--format" => play.format = Some(value()?),
+                "--width" => play.window_width = parse_number(&flag, &value()?)?,
+                "--height" => play.window_height = parse_number(&flag, &value()?)?,
+                "--load" => play.load = Some(PathBuf::from(value()?)),
+                "--save-file" => play.save_file = Some(PathBuf::from(value()?)),
+                "--record" => play.record = Some(PathBuf::from(value()?)),
+                "--undo-depth" => play.undo_depth = parse_number(&flag, &value()?)?,
+                "--port" => play.port = parse_number(&flag, &value()?)?,
+                "--game" => play.game_id = Some(value()?),
+                "--spectate-port" => play.spectate_port = Some(parse_number(&flag, &value()?)?),
+                "--spectate-delay" => {
+                    play.spectate_delay = Some(parse_number(&flag, &value()?)?)
+                },
+                "--delay" => delay = Duration::from_secs(parse_number(&flag, &value()?)?),
+                "--view" => {
+                    view = match value()?.as_str() {
+                        "neutral" => SpectatorView::Neutral,
+                        "1" => SpectatorView::Player(Player::Player1),
+                        "2" => SpectatorView::Player(Player::Player2),
+                        "coach" => SpectatorView::Coach,
+                        other => {
+                            return Err(format!(
+                                "--view must be neutral, 1, 2 or coach, not {}",
+                                other
+                            ))
+                        },
+                    }
+                },
+                "--seed" => play.seed = Some(parse_number(&flag, &value()?)?),
+                "--first-player" => {
+                    play.first_player = match value()?.as_str() {
+                        "1" => Player::Player1,
+                        "2" => Player::Player2,
+                        other => {
+                            return Err(format!("--first-player must be 1 or 2, not {}", other))
+                        },
+                    }
+                },
+                _ if flag.starts_with('-') => return Err(format!("unknown option {}", flag)),
+                _ => positional.push(arg),
+            }
+        }
+
+        let command = if help {
+            Command::Help
+        } else {
+            match subcommand.as_deref() {
+                Some("validate") => match positional.as_slice() {
+                    [deck] => Command::Validate { deck: deck.clone(), format: play.format },
+                    [] => return Err("validate needs a deck".to_string()),
+                    _ => return Err("validate takes one deck".to_string()),
+                },
+                Some("replay") => match positional.as_slice() {
+                    [file] => Command::Replay { file: PathBuf::from(file), options: play },
+                    [] => return Err("replay needs a recording".to_string()),
+                    _ => return Err("replay takes one recording".to_string()),
+                },
+                Some("join") => match positional.as_slice() {
+                    [address] => Command::Join { address: address.clone(), options: play },
+                    [] => return Err("join needs an address".to_string()),
+                    _ => return Err("join takes one address".to_string()),
+                },
+                Some("list-decks") if positional.is_empty() => Command::ListDecks,
+                _ if !positional.is_empty() => {
+                    return Err(format!("unexpected argument {}", positional[0]))
+                },
+                Some("list-decks") | Some("play") | None => Command::Play(play),
+                Some("host") => Command::Host(play),
+                Some("watch") => Command::Watch { view, delay, options: play },
+                Some(other) => unreachable!("unhandled subcommand {}", other),
+            }
+        };
+
+        Ok(Cli { card_root, command })
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn parse(args: &str) -> Result<Cli, String>
+    {
+        Cli::parse(args.split_whitespace().map(str::to_string))
+    }
+
+    #[test]
+    fn play_is_the_default_command()
+    {
+        let cli = parse("--card-root cards --deck1 a.txt --seed=12 --first-player 2").unwrap();
+        assert_eq!(cli.card_root, Some(PathBuf::from("cards")));
+        match cli.command {
+            Command::Play(options) => {
+                assert_eq!(options.deck1.as_deref(), Some("a.txt"));
+                assert_eq!(options.deck2, None);
+                assert_eq!(options.seed, Some(12));
+                assert_eq!(options.first_player, Player::Player2);
+                assert_eq!(options.undo_depth, DEFAULT_UNDO_DEPTH);
+            },
+            other => panic!("expected play, got {:?}", other),
+        }
+        assert!(matches!(parse("play").unwrap().command, Command::Play(_)));
+    }
+
+    #[test]
+    fn subcommands_and_their_arguments()
+    {
+        match parse("validate deck.txt --format=standard").unwrap().command {
+            Command::Validate { deck, format } => {
+                assert_eq!(deck, "deck.txt");
+                assert_eq!(format.as_deref(), Some("standard"));
+            },
+            other => panic!("expected validate, got {:?}", other),
+        }
+        match parse("replay game.json --width 800").unwrap().command {
+            Command::Replay { file, options } => {
+                assert_eq!(file, PathBuf::from("game.json"));
+                assert_eq!(options.window_width, 800);
+            },
+            other => panic!("expected replay, got {:?}", other),
+        }
+        match parse("join 127.0.0.1 --port 9000 --game abc").unwrap().command {
+            Command::Join { address, options } => {
+                assert_eq!(address, "127.0.0.1");
+                assert_eq!(options.port, 9000);
+                assert_eq!(options.game_id.as_deref(), Some("abc"));
+            },
+            other => panic!("expected join, got {:?}", other),
+        }
+        match parse("watch --view coach --delay 30").unwrap().command {
+            Command::Watch { view, delay, .. } => {
+                assert_eq!(view, SpectatorView::Coach);
+                assert_eq!(delay, Duration::from_secs(30));
+            },
+            other => panic!("expected watch, got {:?}", other),
+        }
+        assert!(matches!(parse("host --spectate-port 7778").unwrap().command, Command::Host(_)));
+        assert!(matches!(parse("list-decks").unwrap().command, Command::ListDecks));
+        assert!(matches!(parse("help").unwrap().command, Command::Help));
+        assert!(matches!(parse("join --help").unwrap().command, Command::Help));
+    }
+
+    #[test]
+    fn bad_arguments_are_errors()
+    {
+        assert_eq!(parse("host --port").unwrap_err(), "--port needs a value");
+        assert_eq!(parse("--seed=abc").unwrap_err(), "--seed expects a number, got \"abc\"");
+        assert_eq!(
+            parse("watch --view 3").unwrap_err(),
+            "--view must be neutral, 1, 2 or coach, not 3"
+        );
+        assert_eq!(parse("--first-player 3").unwrap_err(), "--first-player must be 1 or 2, not 3");
+        assert_eq!(parse("--colour blue").unwrap_err(), "unknown option --colour");
+        assert_eq!(parse("validate").unwrap_err(), "validate needs a deck");
+        assert_eq!(parse("join a b").unwrap_err(), "join takes one address");
+        assert_eq!(parse("play extra").unwrap_err(), "unexpected argument extra");
+        assert_eq!(parse("list-decks extra").unwrap_err(), "unexpected argument extra");
+    }
+}
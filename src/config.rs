@@ -0,0 +1,179 @@
+use serde::Deserialize;
+use std::env;
+use std::fmt;
+use std::fs::{read_dir, read_to_string};
+use std::path::{Path, PathBuf};
+
+pub const CARD_ROOT_ENV_VAR: &str = "TCGCLIENT_CARD_ROOT";
+const CONFIG_DIR_NAME: &str = "tcgclient";
+const CONFIG_FILE_NAME: &str = "config.toml";
+const DEFAULT_CARD_ROOT: &str = "cards";
+
+/// Contents of `config.toml` in the user's config dir. Everything is optional
+/// so an empty (or missing) file is fine.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ConfigFile
+{
+    pub card_root: Option<PathBuf>,
+}
+
+impl ConfigFile
+{
+    /// `$XDG_CONFIG_HOME/tcgclient/config.toml`, falling back to `~/.config`.
+    pub fn path() -> Option<PathBuf>
+    {
+        let config_dir = match env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        };
+        Some(config_dir.join(CONFIG_DIR_NAME).join(CONFIG_FILE_NAME))
+    }
+
+    /// Ok(None) if there's no config file at all.
+    pub fn load(path: &Path) -> Result<Option<Self>, String>
+    {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = read_to_string(path)
+            .map_err(|e| format!("Problem reading config file {:?}: {}", path, e))?;
+        let config = toml::from_str(&contents)
+            .map_err(|e| format!("Problem parsing config file {:?}: {}", path, e))?;
+        Ok(Some(config))
+    }
+}
+
+/// Which layer the card root came from.
+#[derive(Clone, Debug)]
+pub enum CardRootSource
+{
+    CommandLine,
+    Environment,
+    ConfigFile(PathBuf),
+    Default,
+}
+
+impl fmt::Display for CardRootSource
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self {
+            CardRootSource::CommandLine => write!(f, "command line flag --card-root"),
+            CardRootSource::Environment => write!(f, "environment variable {}", CARD_ROOT_ENV_VAR),
+            CardRootSource::ConfigFile(path) => write!(f, "config file {:?}", path),
+            CardRootSource::Default => write!(f, "default"),
+        }
+    }
+}
+
+/// The directory holding the card images (one subdirectory per set) and the
+/// `decks/` directory.
+#[derive(Clone, Debug)]
+pub struct CardLibrary
+{
+    pub root: PathBuf,
+    pub source: CardRootSource,
+}
+
+impl CardLibrary
+{
+    /// Precedence, highest first: command line, environment, config file,
+    /// then `./cards`.
+    pub fn resolve(command_line_root: Option<&Path>) -> Result<Self, String>
+    {
+        if let Some(root) = command_line_root {
+            let root = root.to_path_buf();
+            return Ok(CardLibrary { root, source: CardRootSource::CommandLine });
+        }
+
+        if let Some(root) = env::var_os(CARD_ROOT_ENV_VAR) {
+            if !root.is_empty() {
+                return Ok(CardLibrary { root: root.into(), source: CardRootSource::Environment });
+            }
+        }
+
+        if let Some(config_path) = ConfigFile::path() {
+            if let Some(ConfigFile { card_root: Some(root) }) = ConfigFile::load(&config_path)? {
+                // Relative paths in the config file are relative to the file itself
+                let root = match config_path.parent() {
+                    Some(config_dir) => config_dir.join(root),
+                    None => root,
+                };
+                return Ok(CardLibrary { root, source: CardRootSource::ConfigFile(config_path) });
+            }
+        }
+
+        Ok(CardLibrary { root: PathBuf::from(DEFAULT_CARD_ROOT), source: CardRootSource::Default })
+    }
+
+    pub fn decks_dir(&self) -> PathBuf
+    {
+        self.root.join("decks")
+    }
+
+    pub fn deck_path(&self, deck_filename: &str) -> PathBuf
+    {
+        self.decks_dir().join(deck_filename)
+    }
+
+    pub fn saves_dir(&self) -> PathBuf
+    {
+        self.root.join("saves")
+    }
+
+    pub fn replays_dir(&self) -> PathBuf
+    {
+        self.root.join("replays")
+    }
+
+    /// `deck` as given if there's a file there, otherwise relative to `decks/`.
+    pub fn find_deck(&self, deck: &str) -> PathBuf
+    {
+        let as_given = Path::new(deck);
+        if as_given.is_file() {
+            as_given.to_path_buf()
+        } else {
+            self.deck_path(deck)
+        }
+    }
+
+    /// Every `.txt` file under `decks/`, relative to it and sorted, so decks in
+    /// the same subdirectory end up next to each other.
+    pub fn deck_files(&self) -> Vec<PathBuf>
+    {
+        fn walk(dir: &Path, relative: &Path, found: &mut Vec<PathBuf>)
+        {
+            let Ok(entries) = read_dir(dir) else {
+                return;
+            };
+            for entry in entries.filter_map(|entry| entry.ok()) {
+                let path = entry.path();
+                let relative = relative.join(entry.file_name());
+                if path.is_dir() {
+                    walk(&path, &relative, found);
+                } else if path.extension().map(|ext| ext == "txt").unwrap_or(false) {
+                    found.push(relative);
+                }
+            }
+        }
+
+        let mut found = Vec::new();
+        walk(&self.decks_dir(), Path::new(""), &mut found);
+        found.sort();
+        found
+    }
+
+    pub fn image_path(&self, set: &str, card: &str) -> PathBuf
+    {
+        self.root.join(set).join(card).with_extension("jpg")
+    }
+}
+
+impl fmt::Display for CardLibrary
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "Card library: {:?} (from {})", self.root, self.source)
+    }
+}
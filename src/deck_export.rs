@@ -0,0 +1,246 @@
+//! Turning piles back into deck lists, in the native format or the
+//! "4 Flareon ex DS 108" export format (see `deck_import`).
+
+use crate::card_db::CardDb;
+use crate::deck_import::{ExportLine, SetTable};
+use crate::deck_list::{DeckEntry, DeckList};
+use crate::load_cards::CardIndexer;
+use crate::state::{Card, CardLayout, Pile};
+
+/// Every card the layout owns, wherever it currently is.
+pub fn layout_cards(layout: &CardLayout) -> Pile
+{
+    let mut cards = Vec::new();
+    for slot in layout.slots.iter() {
+        cards.extend(slot.cards.iter());
+    }
+    cards.extend(layout.hand.iter());
+    cards.extend(layout.discard.iter());
+    cards.extend(layout.deck.iter());
+    cards.extend(layout.lost_zone.iter());
+    cards.extend(layout.prizes.iter().map(|prize| prize.card));
+    cards.extend(layout.stadium.iter());
+    cards
+}
+
+/// Counts each card, keeping them in the order they first appear.
+fn count_cards(pile: &[Card]) -> Vec<(Card, u32)>
+{
+    let mut counts: Vec<(Card, u32)> = Vec::new();
+    for card in pile.iter() {
+        match counts.iter_mut().find(|(c, _)| c == card) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((*card, 1)),
+        }
+    }
+    counts
+}
+
+/// How many of each card name are in the pile, most first, so different
+/// printings of a card count together.
+pub fn count_by_name(pile: &[Card], indexer: &CardIndexer, db: &CardDb) -> Vec<(String, u32)>
+{
+    let mut counts: Vec<(String, u32)> = Vec::new();
+    for (card, count) in count_cards(pile) {
+        let name = match db.name(card) {
+            Some(name) => name.to_string(),
+            None => indexer.identity(card).1.to_string(),
+        };
+        match counts.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, total)) => *total += count,
+            None => counts.push((name, count)),
+        }
+    }
+    counts.sort_by(|(name1, count1), (name2, count2)| count2.cmp(count1).then(name1.cmp(name2)));
+    counts
+}
+
+/// Entries are sectioned by supertype when there's metadata for every card.
+pub fn to_deck_list(pile: &[Card], indexer: &CardIndexer, db: &CardDb) -> DeckList
+{
+    let mut entries: Vec<DeckEntry> = count_cards(pile)
+        .into_iter()
+        .map(|(card, count)| {
+            let (set, card_id) = indexer.identity(card);
+            DeckEntry {
+                count,
+                set: set.to_string(),
+                card: card_id.to_string(),
+                section: db.supertype(card).map(|supertype| supertype.into()),
+                line: 0,
+            }
+        })
+        .collect();
+    if entries.iter().all(|entry| entry.section.is_some()) {
+        // Stable, so cards keep their order within each section
+        entries.sort_by_key(|entry| entry.section);
+    } else {
+        entries.iter_mut().for_each(|entry| entry.section = None);
+    }
+    DeckList { entries }
+}
+
+/// "flareon-ex-delta-species-ds-108" -> "Flareon ex Delta Species", "108"
+fn name_and_number(card_id: &str, abbrev: &str) -> (String, String)
+{
+    let (rest, number) = card_id.rsplit_once('-').unwrap_or((card_id, ""));
+    let name_slug = rest.strip_suffix(&format!("-{}", abbrev)).unwrap_or(rest);
+    let name = name_slug
+        .split('-')
+        .map(|word| match word {
+            "ex" | "gx" | "v" | "vmax" | "vstar" => word.to_string(),
+            _ => {
+                let mut chars = word.chars();
+                match chars.next() {
+                    Some(first) => first.to_uppercase().chain(chars).collect(),
+                    None => String::new(),
+                }
+            },
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    (name, number.to_string())
+}
+
+/// Uses the card's metadata for its name, set code and number where there is
+/// some, and otherwise works them out from the image name.
+pub fn to_export_lines(
+    pile: &[Card],
+    indexer: &CardIndexer,
+    sets: &SetTable,
+    db: &CardDb,
+) -> Vec<ExportLine>
+{
+    count_cards(pile)
+        .into_iter()
+        .map(|(card, count)| {
+            if let Some(info) = db.get(card) {
+                if let (Some(set_code), Some(number)) = (&info.set, &info.number) {
+                    return ExportLine {
+                        count,
+                        name: info.name.clone(),
+                        set_code: set_code.clone(),
+                        number: number.clone(),
+                    };
+                }
+            }
+            let (set, card_id) = indexer.identity(card);
+            let set_code = match sets.code_for_dir(set) {
+                Some(code) => code.to_string(),
+                None => set.to_uppercase(),
+            };
+            let (_, abbrev) = sets.lookup(&set_code);
+            let (name, number) = name_and_number(card_id, &abbrev);
+            ExportLine { count, name, set_code, number }
+        })
+        .collect()
+}
+
+pub fn export_native(pile: &[Card], indexer: &CardIndexer, db: &CardDb) -> String
+{
+    to_deck_list(pile, indexer, db).to_string()
+}
+
+pub fn export_text(pile: &[Card], indexer: &CardIndexer, sets: &SetTable, db: &CardDb) -> String
+{
+    let lines = to_export_lines(pile, indexer, sets, db);
+    let mut text = String::new();
+    for line in lines.iter() {
+        text.push_str(&line.to_string());
+        text.push('\n');
+    }
+    let total: u32 = lines.iter().map(|line| line.count).sum();
+    text.push_str(&format!("\nTotal Cards: {}\n", total));
+    text
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::deck_list::Section;
+
+    /// A pile of the card ids given, with metadata for the ones that have some.
+    fn pile(cards: &[(&str, Option<&str>)]) -> (Pile, CardIndexer, CardDb)
+    {
+        let mut indexer = CardIndexer::default();
+        let mut db = CardDb::default();
+        let pile = cards
+            .iter()
+            .map(|(card_id, json)| {
+                let card = indexer.intern("ds", card_id);
+                if let Some(json) = json {
+                    db.insert(card, serde_json::from_str(json).unwrap());
+                }
+                card
+            })
+            .collect();
+        (pile, indexer, db)
+    }
+
+    /// The card id, count and section of each entry.
+    fn entries(list: &DeckList) -> Vec<(&str, u32, Option<Section>)>
+    {
+        list.entries.iter().map(|entry| (entry.card.as_str(), entry.count, entry.section)).collect()
+    }
+
+    const RARE_CANDY: &str = r#"{"name": "Rare Candy", "supertype": "Trainer"}"#;
+    const FIRE_ENERGY: &str = r#"{"name": "Fire Energy", "supertype": "Energy"}"#;
+    const EEVEE: &str = r#"{"name": "Eevee", "supertype": "Pokémon"}"#;
+    const FLAREON: &str = r#"{"name": "Flareon ex", "supertype": "Pokémon"}"#;
+
+    #[test]
+    fn sections_are_sorted_when_every_card_has_metadata()
+    {
+        let (pile, indexer, db) = pile(&[
+            ("fire-energy", Some(FIRE_ENERGY)),
+            ("rare-candy", Some(RARE_CANDY)),
+            ("flareon-ex", Some(FLAREON)),
+            ("fire-energy", Some(FIRE_ENERGY)),
+            ("eevee", Some(EEVEE)),
+            ("flareon-ex", Some(FLAREON)),
+        ]);
+        assert_eq!(
+            entries(&to_deck_list(&pile, &indexer, &db)),
+            vec![
+                ("flareon-ex", 2, Some(Section::Pokemon)),
+                ("eevee", 1, Some(Section::Pokemon)),
+                ("rare-candy", 1, Some(Section::Trainer)),
+                ("fire-energy", 2, Some(Section::Energy)),
+            ]
+        );
+    }
+
+    #[test]
+    fn one_card_without_metadata_leaves_the_list_unsectioned()
+    {
+        let (pile, indexer, db) = pile(&[
+            ("fire-energy", Some(FIRE_ENERGY)),
+            ("rare-candy", None),
+            ("flareon-ex", Some(FLAREON)),
+        ]);
+        assert_eq!(
+            entries(&to_deck_list(&pile, &indexer, &db)),
+            vec![("fire-energy", 1, None), ("rare-candy", 1, None), ("flareon-ex", 1, None)]
+        );
+    }
+
+    #[test]
+    fn counting_by_name_puts_the_most_first()
+    {
+        let (pile, indexer, db) = pile(&[
+            ("rare-candy", None),
+            ("flareon-ex", Some(FLAREON)),
+            ("flareon-ex-alt", Some(FLAREON)),
+            ("eevee", Some(EEVEE)),
+        ]);
+        assert_eq!(
+            count_by_name(&pile, &indexer, &db),
+            vec![
+                ("Flareon ex".to_string(), 2),
+                ("Eevee".to_string(), 1),
+                ("rare-candy".to_string(), 1),
+            ]
+        );
+    }
+}
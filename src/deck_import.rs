@@ -0,0 +1,322 @@
+//! Importing the "4 Gardevoir ex SVI 86" deck lists that PTCG Live, PTCGO
+//! and most tournament sites export.
+//!
+//! Set codes are mapped to image directories by `sets.toml` in the card root:
+//!
+//! ```toml
+//! [DS]
+//! dir = "delta-species"
+//! # Optional, defaults to the lowercased set code
+//! abbrev = "ds"
+//! ```
+//!
+//! A card is then resolved by finding the image in that directory whose name
+//! ends in `-<abbrev>-<number>`, e.g. `flareon-ex-delta-species-ds-108.jpg`.
+
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{read_dir, read_to_string};
+
+use crate::config::CardLibrary;
+use crate::deck_list::{DeckEntry, DeckList, Section};
+
+const SETS_FILE_NAME: &str = "sets.toml";
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct SetInfo
+{
+    pub dir: String,
+    pub abbrev: Option<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SetTable
+{
+    sets: HashMap<String, SetInfo>,
+}
+
+impl SetTable
+{
+    /// An empty table if there's no `sets.toml`.
+    pub fn load(library: &CardLibrary) -> Result<Self, String>
+    {
+        let path = library.root.join(SETS_FILE_NAME);
+        if !path.exists() {
+            return Ok(Default::default());
+        }
+        let contents =
+            read_to_string(&path).map_err(|e| format!("Problem reading {:?}: {}", path, e))?;
+        let sets: HashMap<String, SetInfo> =
+            toml::from_str(&contents).map_err(|e| format!("Problem parsing {:?}: {}", path, e))?;
+        let sets = sets.into_iter().map(|(code, info)| (code.to_uppercase(), info)).collect();
+        Ok(SetTable { sets })
+    }
+
+    /// (image directory, abbreviation used in image names) for a set code.
+    /// Unknown codes are assumed to be their own directory and abbreviation.
+    pub fn lookup(&self, set_code: &str) -> (String, String)
+    {
+        match self.sets.get(&set_code.to_uppercase()) {
+            Some(info) => {
+                let abbrev = info.abbrev.clone().unwrap_or_else(|| set_code.to_lowercase());
+                (info.dir.clone(), abbrev)
+            },
+            None => (set_code.to_lowercase(), set_code.to_lowercase()),
+        }
+    }
+
+    /// Set code for an image directory, the reverse of `lookup`.
+    pub fn code_for_dir(&self, dir: &str) -> Option<&str>
+    {
+        self.sets.iter().find(|(_, info)| info.dir == dir).map(|(code, _)| code.as_str())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ExportLine
+{
+    pub count: u32,
+    pub name: String,
+    pub set_code: String,
+    pub number: String,
+}
+
+impl ExportLine
+{
+    /// `* 4 Gardevoir ex SVI 86`, with or without the `*`.
+    fn parse(line: &str) -> Option<Self>
+    {
+        let line = line.strip_prefix('*').unwrap_or(line);
+        let mut words: Vec<&str> = line.split_whitespace().collect();
+        if words.len() < 4 {
+            return None;
+        }
+        let count = words.remove(0).parse::<u32>().ok()?;
+        let number = words.pop()?.to_string();
+        let set_code = words.pop()?.to_string();
+        let name = words.join(" ");
+        Some(ExportLine { count, name, set_code, number })
+    }
+}
+
+impl fmt::Display for ExportLine
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "{} {} {} {}", self.count, self.name, self.set_code, self.number)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct UnresolvedCard
+{
+    pub line: usize,
+    pub text: String,
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ImportedDeck
+{
+    pub deck: DeckList,
+    pub unresolved: Vec<UnresolvedCard>,
+}
+
+/// Tells an export list apart from a native one: the first card line of an
+/// export has the count and the card on the same line.
+pub fn looks_like_export(text: &str) -> bool
+{
+    text.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !is_ignored_line(line))
+        .find(|line| section_header(line).is_none())
+        .map(|line| ExportLine::parse(line).is_some())
+        .unwrap_or(false)
+}
+
+fn is_ignored_line(line: &str) -> bool
+{
+    line.starts_with("****")
+        || (line.starts_with('#') && !line.starts_with("##"))
+        || line.to_lowercase().starts_with("total cards")
+}
+
+/// `Pokémon: 12` (PTCG Live) or `##Pokémon - 12` (PTCGO).
+fn section_header(line: &str) -> Option<Section>
+{
+    let header = match line.strip_prefix("##") {
+        Some(header) => header.split(" - ").next().unwrap_or(header),
+        None => line,
+    };
+    match header.split(':').next().unwrap_or(header).trim().to_lowercase().as_str() {
+        "pokémon" | "pokemon" => Some(Section::Pokemon),
+        "trainer" | "trainers" | "trainer cards" => Some(Section::Trainer),
+        "energy" | "energies" => Some(Section::Energy),
+        _ => None,
+    }
+}
+
+fn normalize_number(number: &str) -> String
+{
+    let number = number.to_lowercase();
+    if number.chars().all(|c| c.is_ascii_digit()) {
+        let trimmed = number.trim_start_matches('0');
+        if trimmed.is_empty() { "0".to_string() } else { trimmed.to_string() }
+    } else {
+        number
+    }
+}
+
+fn slugify(name: &str) -> String
+{
+    let mut slug = String::new();
+    for c in name.to_lowercase().chars() {
+        let c = match c {
+            'é' => 'e',
+            c => c,
+        };
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+/// Finds the image id (file name without `.jpg`) for a card in `dir`.
+fn resolve_card(
+    library: &CardLibrary,
+    sets: &SetTable,
+    card: &ExportLine,
+) -> Result<(String, String), String>
+{
+    let (dir, abbrev) = sets.lookup(&card.set_code);
+    let entries = read_dir(library.root.join(&dir))
+        .map_err(|e| format!("no image directory {:?} for set {} ({})", dir, card.set_code, e))?;
+
+    let suffix = format!("-{}-{}", abbrev, normalize_number(&card.number));
+    let mut candidates: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter_map(|file_name| file_name.strip_suffix(".jpg").map(|s| s.to_string()))
+        .filter(|stem| stem.ends_with(&suffix))
+        .collect();
+
+    // Alternate scans can share a set and number, so fall back to matching the
+    // card's name when there's more than one
+    if candidates.len() > 1 {
+        let slug = slugify(&card.name);
+        candidates.retain(|stem| stem.starts_with(&slug));
+    }
+    match candidates.len() {
+        1 => Ok((dir, candidates.remove(0))),
+        0 => Err(format!("no image for {} {} in {:?}", card.set_code, card.number, dir)),
+        _ => Err(format!("more than one image matches {} {}", card.set_code, card.number)),
+    }
+}
+
+pub fn import_deck(library: &CardLibrary, sets: &SetTable, text: &str) -> ImportedDeck
+{
+    let mut imported = ImportedDeck::default();
+    let mut section = None;
+
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.trim();
+        if line.is_empty() || is_ignored_line(line) {
+            continue;
+        }
+        if let Some(header) = section_header(line) {
+            section = Some(header);
+            continue;
+        }
+
+        let Some(card) = ExportLine::parse(line) else {
+            imported.unresolved.push(UnresolvedCard {
+                line: line_number,
+                text: line.to_string(),
+                reason: "expected \"<count> <name> <set> <number>\"".to_string(),
+            });
+            continue;
+        };
+        match resolve_card(library, sets, &card) {
+            Ok((set, card_id)) => imported.deck.entries.push(DeckEntry {
+                count: card.count,
+                set,
+                card: card_id,
+                section,
+                line: line_number,
+            }),
+            Err(reason) => imported.unresolved.push(UnresolvedCard {
+                line: line_number,
+                text: line.to_string(),
+                reason,
+            }),
+        }
+    }
+    imported
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn export_lines_split_into_count_name_set_and_number()
+    {
+        let line = ExportLine::parse("* 4 Gardevoir ex SVI 86").unwrap();
+        assert_eq!(line.count, 4);
+        assert_eq!(line.name, "Gardevoir ex");
+        assert_eq!((line.set_code.as_str(), line.number.as_str()), ("SVI", "86"));
+        assert_eq!(line.to_string(), "4 Gardevoir ex SVI 86");
+
+        let line = ExportLine::parse("1 Flareon ex δ DS 108").unwrap();
+        assert_eq!(line.name, "Flareon ex δ");
+        assert_eq!(ExportLine::parse("2 Pikachu SVI").map(|line| line.name), None);
+        assert_eq!(ExportLine::parse("Four Gardevoir ex SVI 86").map(|line| line.name), None);
+    }
+
+    #[test]
+    fn exports_are_told_apart_from_native_lists()
+    {
+        let export = "****** Pokémon Trading Card Game Deck List ******\n\n\
+                      ##Pokémon - 12\n\n* 4 Gardevoir ex SVI 86\n";
+        assert!(looks_like_export(export));
+        assert!(looks_like_export("Pokémon: 12\n4 Gardevoir ex SVI 86\n"));
+        assert!(!looks_like_export("Pokémon\n4\nsv1\ngardevoir-ex-sv1-86\n"));
+        assert!(!looks_like_export(""));
+    }
+
+    #[test]
+    fn section_headers_from_either_export()
+    {
+        assert_eq!(section_header("Pokémon: 12"), Some(Section::Pokemon));
+        assert_eq!(section_header("##Pokemon - 12"), Some(Section::Pokemon));
+        assert_eq!(section_header("Trainer: 36"), Some(Section::Trainer));
+        assert_eq!(section_header("##Trainer Cards - 36"), Some(Section::Trainer));
+        assert_eq!(section_header("Energy: 12"), Some(Section::Energy));
+        assert_eq!(section_header("4 Gardevoir ex SVI 86"), None);
+    }
+
+    #[test]
+    fn numbers_lose_leading_zeros_but_not_letters()
+    {
+        assert_eq!(normalize_number("086"), "86");
+        assert_eq!(normalize_number("000"), "0");
+        assert_eq!(normalize_number("TG05"), "tg05");
+        assert_eq!(normalize_number("SV107"), "sv107");
+    }
+
+    #[test]
+    fn names_are_slugified_like_image_names()
+    {
+        assert_eq!(slugify("Gardevoir ex"), "gardevoir-ex");
+        assert_eq!(slugify("Pokémon Catcher"), "pokemon-catcher");
+        assert_eq!(slugify("Professor's Research"), "professor-s-research");
+        assert_eq!(slugify("Flareon ex δ"), "flareon-ex");
+        assert_eq!(slugify("  Boss's Orders  "), "boss-s-orders");
+    }
+}
@@ -0,0 +1,327 @@
+//! The native deck-list format.
+//!
+//! Each entry is three lines: a count, the set (the directory the image lives
+//! in) and the card id (the image's file name without `.jpg`):
+//!
+//! ```text
+//! # Comments start with '#'
+//! Pokémon
+//! 4
+//! ds
+//! flareon-ex-delta-species-ds-108
+//!
+//! Trainer: 20
+//! 3
+//! ...
+//! ```
+//!
+//! Blank lines and comments can go anywhere. Section headers (`Pokémon`,
+//! `Trainer`, `Energy`, optionally followed by `:` and a count which is
+//! ignored) can only go where a count is expected.
+
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Section
+{
+    Pokemon,
+    Trainer,
+    Energy,
+}
+
+impl Section
+{
+    fn from_header(line: &str) -> Option<Self>
+    {
+        let name = match line.split_once(':') {
+            Some((name, rest)) => {
+                let rest = rest.trim();
+                if !rest.is_empty() && rest.parse::<u32>().is_err() {
+                    return None;
+                }
+                name
+            },
+            None => line,
+        };
+        match name.trim().to_lowercase().as_str() {
+            "pokémon" | "pokemon" => Some(Section::Pokemon),
+            "trainer" | "trainers" => Some(Section::Trainer),
+            "energy" | "energies" => Some(Section::Energy),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Section
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self {
+            Section::Pokemon => write!(f, "Pokémon"),
+            Section::Trainer => write!(f, "Trainer"),
+            Section::Energy => write!(f, "Energy"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DeckEntry
+{
+    pub count: u32,
+    pub set: String,
+    pub card: String,
+    pub section: Option<Section>,
+    /// Line of the count, for error messages. 0 if the entry didn't come from a file.
+    pub line: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DeckList
+{
+    pub entries: Vec<DeckEntry>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DeckListError
+{
+    MalformedLine
+    {
+        line: usize, content: String
+    },
+    BadCount
+    {
+        line: usize, count: String
+    },
+}
+
+enum Expecting
+{
+    Count,
+    Set
+    {
+        count: u32, count_line: usize
+    },
+    Card
+    {
+        count: u32, count_line: usize, set: String
+    },
+}
+
+impl DeckList
+{
+    pub fn parse(text: &str) -> Result<Self, Vec<DeckListError>>
+    {
+        let mut entries = Vec::new();
+        let mut errors = Vec::new();
+        let mut section = None;
+        let mut expecting = Expecting::Count;
+
+        let lines = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+        for (line_number, line) in lines {
+            expecting = match expecting {
+                Expecting::Count => {
+                    if let Some(header) = Section::from_header(line) {
+                        section = Some(header);
+                        Expecting::Count
+                    } else {
+                        match line.parse::<u32>() {
+                            Ok(count) => Expecting::Set { count, count_line: line_number },
+                            Err(_) => {
+                                errors.push(DeckListError::BadCount {
+                                    line: line_number,
+                                    count: line.to_string(),
+                                });
+                                Expecting::Count
+                            },
+                        }
+                    }
+                },
+                Expecting::Set { count, count_line } => {
+                    Expecting::Card { count, count_line, set: line.to_string() }
+                },
+                Expecting::Card { count, count_line, set } => {
+                    entries.push(DeckEntry {
+                        count,
+                        set,
+                        card: line.to_string(),
+                        section,
+                        line: count_line,
+                    });
+                    Expecting::Count
+                },
+            };
+        }
+
+        match expecting {
+            Expecting::Count => (),
+            Expecting::Set { count_line, count } => errors.push(DeckListError::MalformedLine {
+                line: count_line,
+                content: format!("{} (missing set and card)", count),
+            }),
+            Expecting::Card { count_line, set, .. } => {
+                errors.push(DeckListError::MalformedLine {
+                    line: count_line,
+                    content: format!("{} (missing card)", set),
+                })
+            },
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(DeckList { entries })
+    }
+
+    pub fn total_cards(&self) -> u32
+    {
+        self.entries.iter().map(|e| e.count).sum()
+    }
+
+    /// Every distinct (set, card) in the list, in the order they first appear.
+    pub fn distinct_cards(&self) -> Vec<(&str, &str)>
+    {
+        let mut cards: Vec<(&str, &str)> = Vec::new();
+        for entry in self.entries.iter() {
+            let card = (entry.set.as_str(), entry.card.as_str());
+            if !cards.contains(&card) {
+                cards.push(card);
+            }
+        }
+        cards
+    }
+}
+
+/// Writes the list back out in the format `parse` reads.
+impl fmt::Display for DeckList
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        let mut section = None;
+        for (i, entry) in self.entries.iter().enumerate() {
+            if entry.section != section {
+                if let Some(header) = entry.section {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    writeln!(f, "{}", header)?;
+                }
+                section = entry.section;
+            }
+            writeln!(f, "{}", entry.count)?;
+            writeln!(f, "{}", entry.set)?;
+            writeln!(f, "{}", entry.card)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    const SAMPLE: &str = "\
+# A test deck
+Pokémon: 5
+4
+ds
+flareon-ex-delta-species-ds-108
+
+1
+mt
+pachirisu-mt-86
+# Halfway
+Trainer
+3
+pk
+rare-candy-pk-89
+
+Energy: 12
+12
+energy
+fire-energy
+";
+
+    fn summary(list: &DeckList) -> Vec<(u32, &str, &str, Option<Section>)>
+    {
+        list.entries
+            .iter()
+            .map(|e| (e.count, e.set.as_str(), e.card.as_str(), e.section))
+            .collect()
+    }
+
+    #[test]
+    fn parses_sections_and_skips_comments()
+    {
+        let list = DeckList::parse(SAMPLE).unwrap();
+        assert_eq!(
+            summary(&list),
+            vec![
+                (4, "ds", "flareon-ex-delta-species-ds-108", Some(Section::Pokemon)),
+                (1, "mt", "pachirisu-mt-86", Some(Section::Pokemon)),
+                (3, "pk", "rare-candy-pk-89", Some(Section::Trainer)),
+                (12, "energy", "fire-energy", Some(Section::Energy)),
+            ]
+        );
+        assert_eq!(list.entries[0].line, 3);
+        assert_eq!(list.total_cards(), 20);
+    }
+
+    #[test]
+    fn entries_before_any_header_have_no_section()
+    {
+        let list = DeckList::parse("2\nds\nflareon\n").unwrap();
+        assert_eq!(summary(&list), vec![(2, "ds", "flareon", None)]);
+    }
+
+    #[test]
+    fn reports_bad_counts()
+    {
+        let errors = DeckList::parse("Pokémon\nfour\nds\nflareon\n").unwrap_err();
+        assert_eq!(
+            errors[0],
+            DeckListError::BadCount { line: 2, count: "four".to_string() }
+        );
+    }
+
+    #[test]
+    fn reports_entries_cut_short()
+    {
+        let errors = DeckList::parse("4\nds\nflareon\n2\nmt\n").unwrap_err();
+        assert_eq!(
+            errors,
+            vec![DeckListError::MalformedLine { line: 4, content: "mt (missing card)".to_string() }]
+        );
+
+        let errors = DeckList::parse("4\n").unwrap_err();
+        assert_eq!(
+            errors,
+            vec![DeckListError::MalformedLine {
+                line: 1,
+                content: "4 (missing set and card)".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn header_with_a_non_number_count_is_not_a_header()
+    {
+        let errors = DeckList::parse("Trainer: lots\n").unwrap_err();
+        assert!(matches!(errors[0], DeckListError::BadCount { line: 1, .. }));
+    }
+
+    #[test]
+    fn display_round_trips()
+    {
+        let list = DeckList::parse(SAMPLE).unwrap();
+        let written = list.to_string();
+        let reparsed = DeckList::parse(&written).unwrap();
+        assert_eq!(summary(&reparsed), summary(&list));
+        assert_eq!(reparsed.to_string(), written);
+    }
+}
@@ -0,0 +1,184 @@
+//! The screen before a game where each player chooses a deck from `decks/`.
+//! Decks are only loaded once they're highlighted, so a big collection
+//! doesn't slow down startup.
+
+use std::path::{Path, PathBuf};
+
+use crate::card_db::CardDb;
+use crate::config::CardLibrary;
+use crate::deck_import::SetTable;
+use crate::input::Input;
+use crate::load_cards::{self, CardIndexer};
+use crate::state::{Card, Player};
+use crate::validation::{self, FormatRules, ValidationReport};
+
+#[derive(Clone, Debug)]
+pub enum DeckPreview
+{
+    NotLoaded,
+    /// The deck couldn't be read; it can't be picked.
+    Broken(String),
+    Loaded
+    {
+        /// Each distinct card with how many copies, in list order.
+        cards: Vec<(Card, u32)>,
+        total: u32,
+        report: ValidationReport,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct PickerDeck
+{
+    /// Relative to `decks/`.
+    pub path: PathBuf,
+    pub preview: DeckPreview,
+}
+
+impl PickerDeck
+{
+    /// The subdirectory the deck is in, e.g. `2009`, or "" at the top level.
+    pub fn group(&self) -> &Path
+    {
+        self.path.parent().unwrap_or(Path::new(""))
+    }
+}
+
+pub struct DeckPicker
+{
+    pub decks: Vec<PickerDeck>,
+    pub highlight: usize,
+    /// Players who still have to pick, in order.
+    pub pickers: Vec<Player>,
+    /// Indices into `decks`, one per player in `pickers` who has picked.
+    pub picks: Vec<usize>,
+    pub rules: FormatRules,
+    // The picker has its own cards, separate from the game's
+    pub indexer: CardIndexer,
+    pub db: CardDb,
+    library: CardLibrary,
+    sets: SetTable,
+}
+
+impl DeckPicker
+{
+    pub fn make(
+        library: &CardLibrary,
+        sets: &SetTable,
+        rules: &FormatRules,
+        pickers: Vec<Player>,
+    ) -> Self
+    {
+        let decks = library
+            .deck_files()
+            .into_iter()
+            .map(|path| PickerDeck { path, preview: DeckPreview::NotLoaded })
+            .collect();
+        DeckPicker {
+            decks,
+            highlight: 0,
+            pickers,
+            picks: Vec::new(),
+            rules: rules.clone(),
+            indexer: CardIndexer::default(),
+            db: CardDb::default(),
+            library: library.clone(),
+            sets: sets.clone(),
+        }
+    }
+
+    /// Who's choosing now, None once everyone has.
+    pub fn picking(&self) -> Option<Player>
+    {
+        self.pickers.get(self.picks.len()).copied()
+    }
+
+    pub fn is_done(&self) -> bool
+    {
+        self.picking().is_none()
+    }
+
+    /// Full paths of the chosen decks, paired with who chose them.
+    pub fn chosen(&self) -> Vec<(Player, PathBuf)>
+    {
+        self.pickers
+            .iter()
+            .zip(self.picks.iter())
+            .map(|(player, &deck)| {
+                (*player, self.library.decks_dir().join(&self.decks[deck].path))
+            })
+            .collect()
+    }
+
+    /// Which player (if any) already chose `deck`.
+    pub fn chosen_by(&self, deck: usize) -> Option<Player>
+    {
+        self.picks.iter().position(|&pick| pick == deck).map(|i| self.pickers[i])
+    }
+
+    pub fn update(&mut self, input: &Input)
+    {
+        use Input as I;
+        match input {
+            I::Left => self.highlight = self.highlight.saturating_sub(1),
+            I::Right if self.highlight + 1 < self.decks.len() => self.highlight += 1,
+            I::Select => {
+                let can_pick = matches!(
+                    self.decks.get(self.highlight).map(|deck| &deck.preview),
+                    Some(DeckPreview::Loaded { .. })
+                );
+                if can_pick && !self.is_done() {
+                    self.picks.push(self.highlight);
+                }
+            },
+            // Lets the previous player change their mind
+            I::Cancel => {
+                self.picks.pop();
+            },
+            _ => (),
+        }
+    }
+
+    /// Loads the highlighted deck if it hasn't been yet, returning any cards
+    /// that are new to the picker so their images can be pre-warmed.
+    pub fn load_highlighted(&mut self) -> Vec<Card>
+    {
+        let Some(deck) = self.decks.get(self.highlight) else {
+            return Vec::new();
+        };
+        if !matches!(deck.preview, DeckPreview::NotLoaded) {
+            return Vec::new();
+        }
+
+        let path = self.library.decks_dir().join(&deck.path);
+        let deck_list = match load_cards::load_deck_list(&self.library, &self.sets, &path) {
+            Ok(deck_list) => deck_list,
+            Err(errors) => {
+                let report = load_cards::error_report(&errors);
+                self.decks[self.highlight].preview = DeckPreview::Broken(report);
+                return Vec::new();
+            },
+        };
+
+        let known = self.indexer.len();
+        let mut cards: Vec<(Card, u32)> = Vec::new();
+        for entry in deck_list.entries.iter() {
+            let card = self.indexer.intern(&entry.set, &entry.card);
+            match cards.iter_mut().find(|(c, _)| *c == card) {
+                Some((_, count)) => *count += entry.count,
+                None => cards.push((card, entry.count)),
+            }
+        }
+        let new_cards: Vec<Card> = (known..self.indexer.len()).collect();
+        for card in new_cards.iter() {
+            if let Err(e) = self.db.load_card(&self.library, &self.indexer, *card) {
+                eprintln!("Bad card metadata: {}", e);
+            }
+        }
+
+        let report = validation::validate(&deck_list, &self.rules, &self.indexer, &self.db);
+        let total = deck_list.total_cards();
+        self.decks[self.highlight].preview = DeckPreview::Loaded { cards, total, report };
+        new_cards
+    }
+}
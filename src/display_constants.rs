@@ -12,6 +12,16 @@ pub const BOARD_SIDE_MARGIN: i32 = 25;
 pub const PLAYER1_SLEEVE_COLOR: Color = Color::RGB(255, 0, 0);
 pub const PLAYER2_SLEEVE_COLOR: Color = Color::RGB(0, 255, 0);
 
+pub const LETTERBOX_COLOR: Color = Color::RGB(30, 30, 30);
+
+pub const PLACEHOLDER_POKEMON_COLOR: Color = Color::RGB(200, 70, 60);
+pub const PLACEHOLDER_TRAINER_COLOR: Color = Color::RGB(70, 110, 200);
+pub const PLACEHOLDER_ENERGY_COLOR: Color = Color::RGB(220, 180, 40);
+pub const PLACEHOLDER_UNKNOWN_COLOR: Color = Color::RGB(130, 130, 130);
+pub const PLACEHOLDER_INNER_COLOR: Color = Color::RGB(240, 235, 220);
+pub const PLACEHOLDER_TEXT_COLOR: Color = Color::RGB(0, 0, 0);
+pub const PLACEHOLDER_BORDER: u32 = 3;
+
 pub const HIGHLIGHT_COLOR: Color = Color::RGB(0, 200, 200);
 pub const SELECTED_COLOR: Color = Color::RGB(0, 0, 255);
 pub const HIGHLIGHT_THICKNESS: u32 = 5;
@@ -51,3 +61,29 @@ pub const SPACE_BETWEEN_DICE: u32 = 5;
 pub const DICE_ROLL_MAT_COLOR: Color = Color::RGB(100, 100, 100);
 pub const DICE_ROLL_MAT_SIDELEN: u32 = 40;
 
+
+pub const PICKER_MARGIN: i32 = 25;
+pub const PICKER_LIST_WIDTH: u32 = 420;
+pub const PICKER_TEXT_SCALE: u32 = 3;
+pub const PICKER_TEXT_COLOR: Color = Color::RGB(0, 0, 0);
+pub const PICKER_GROUP_COLOR: Color = Color::RGB(120, 120, 120);
+pub const PICKER_LEGAL_COLOR: Color = Color::RGB(0, 150, 0);
+pub const PICKER_ILLEGAL_COLOR: Color = Color::RGB(200, 0, 0);
+pub const PICKER_THUMBNAIL_SPACING: u32 = 10;
+
+pub const LOG_PANEL_COLOR: Color = Color::RGB(40, 40, 40);
+pub const LOG_TEXT_COLOR: Color = Color::RGB(230, 230, 230);
+pub const LOG_TITLE_COLOR: Color = Color::RGB(0, 200, 200);
+pub const LOG_TEXT_SCALE: u32 = 2;
+pub const LOG_PADDING: i32 = 10;
+
+pub const CHAT_PANEL_HEIGHT: u32 = 220;
+pub const CHAT_PANEL_COLOR: Color = Color::RGB(20, 20, 50);
+pub const CHAT_TYPING_COLOR: Color = Color::RGB(255, 255, 120);
+
+pub const WAITING_SHADE_COLOR: Color = Color::RGBA(0, 0, 0, 150);
+pub const WAITING_TEXT_SCALE: u32 = 4;
+
+pub const STATUS_COLOR: Color = Color::RGB(40, 40, 40);
+pub const STATUS_TEXT_COLOR: Color = Color::RGB(230, 230, 230);
+pub const STATUS_TEXT_SCALE: u32 = 2;
@@ -1,7 +1,13 @@
 use crate::display_constants::*;
 use sdl2::{libc::DIR, pixels::Color, rect::Rect, render::Texture, sys::div};
+use sdl2::render::BlendMode;
 
-use crate::{load_cards, state};
+use crate::card_db::{CardDb, Supertype};
+use crate::deck_picker::{DeckPicker, DeckPreview};
+use crate::game_log::{GameLog, LogView};
+use crate::texture_cache::TextureCache;
+use crate::view::{self, LayoutView, PlayerView};
+use crate::{deck_export, load_cards, pixel_font, state};
 
 type Renderer = sdl2::render::Canvas<sdl2::video::Window>;
 
@@ -11,6 +17,14 @@ macro_rules! rect {
     };
 }
 
+/// Everything needed to draw a card face.
+pub struct CardArt<'r, 'a>
+{
+    pub textures: &'r mut TextureCache<'a>,
+    pub indexer: &'r load_cards::CardIndexer,
+    pub db: &'r CardDb,
+}
+
 #[derive(Clone, Copy)]
 enum CardDisplaySize
 {
@@ -47,65 +61,84 @@ impl Side
     }
 }
 
+/// Draws the board from the viewer's side, showing only what they can see.
 pub fn draw(
     canvas: &mut Renderer,
-    st: &state::State,
-    card_textures: &Vec<Texture>,
+    view: &PlayerView,
+    art: &mut CardArt,
 ) -> Result<(), String>
 {
-    let (facing_layout, opposing_layout, facing_color, opposing_color) = match &st
-        .currently_viewing
-    {
+    let (facing_layout, opposing_layout, facing_color, opposing_color) = match &view.viewer {
         state::Player::Player1 => {
-            (&st.player1_layout, &st.player2_layout, PLAYER1_SLEEVE_COLOR, PLAYER2_SLEEVE_COLOR)
+            (&view.player1, &view.player2, PLAYER1_SLEEVE_COLOR, PLAYER2_SLEEVE_COLOR)
         },
         state::Player::Player2 => {
-            (&st.player2_layout, &st.player1_layout, PLAYER2_SLEEVE_COLOR, PLAYER1_SLEEVE_COLOR)
+            (&view.player2, &view.player1, PLAYER2_SLEEVE_COLOR, PLAYER1_SLEEVE_COLOR)
         },
     };
-    draw_layout(canvas, facing_layout, Side::Facing, facing_color, card_textures)?;
-    draw_layout(canvas, opposing_layout, Side::Opposing, opposing_color, card_textures)?;
+    draw_layout(canvas, facing_layout, Side::Facing, facing_color, art)?;
+    draw_layout(
+        canvas,
+        opposing_layout,
+        Side::Opposing,
+        opposing_color,
+        art,
+    )?;
 
     // DRAW HIGHLIGHT (AND DECK/DISCARD SEARCH IF APPLICABLE)
     use state::InputMode as IM;
-    match &st.input_mode {
+    match &view.input_mode {
         IM::Selecting(st2) => {
-            if let state::Selection::Discard { .. } = &st2.current_highlight {
-                draw_deck_and_discard_search(&facing_layout.discard, canvas, card_textures)?;
+            if let state::Selection::Discard { player, .. } = &st2.current_highlight {
+                draw_deck_and_discard_search(
+                    &view.layout(*player).discard,
+                    canvas,
+                    art,
+                )?;
             }
 
-            highlight_selection(&st2.current_highlight, HIGHLIGHT_COLOR, &facing_layout, canvas)?;
-            if let Some(card) = st.card_at(&st2.current_highlight) {
-                draw_focused_card(card, canvas, card_textures)?;
+            highlight_selection(&st2.current_highlight, HIGHLIGHT_COLOR, view, canvas)?;
+            if let Some(card) = view.card_at(&st2.current_highlight) {
+                draw_focused_card(card, canvas, art)?;
             }
             for sel in st2.selected.iter() {
-                highlight_selection(&sel, SELECTED_COLOR, &facing_layout, canvas)?;
+                highlight_selection(&sel, SELECTED_COLOR, view, canvas)?;
             }
         },
         IM::Deck => {
             let (x, y) = deck_location(Side::Facing);
             highlight_card_at(x, y, HIGHLIGHT_COLOR, canvas)?;
         },
-        IM::DeckSearch(st2) => {
-            draw_deck_and_discard_search(&facing_layout.deck, canvas, card_textures)?;
+        // Both pick from the part of the deck being shown
+        IM::DeckSearch(st2) | IM::Look { st: st2, .. } => {
+            let shown = &view.deck_shown;
+            draw_deck_and_discard_search(shown, canvas, art)?;
 
-            let (x, y) =
-                deck_and_discard_card_location(st2.current_highlight, facing_layout.deck.len());
+            let (x, y) = deck_and_discard_card_location(st2.current_highlight, shown.len());
             highlight_card_at(x, y, HIGHLIGHT_COLOR, canvas)?;
-            if let Some(card) = st.deck_card_at(st2.current_highlight) {
-                draw_focused_card(card, canvas, card_textures)?;
+            if let Some(card) = shown.get(st2.current_highlight) {
+                draw_focused_card(*card, canvas, art)?;
             }
             for sel in st2.selected.iter() {
-                let (x, y) = deck_and_discard_card_location(*sel, facing_layout.deck.len());
+                let (x, y) = deck_and_discard_card_location(*sel, shown.len());
                 highlight_card_at(x, y, SELECTED_COLOR, canvas)?;
             }
         },
-        IM::Look(_) => (),
-        IM::Move { .. } => (),
+        IM::Observe => {
+            draw_deck_and_discard_search(&view.deck_shown, canvas, art)?;
+            draw_deck_summary(canvas, &view.deck_shown, art)?;
+        },
+        IM::Move { destination, .. } => {
+            if *destination != view.viewer {
+                draw_status(canvas, &format!("Moving to {}'s side", destination))?;
+            }
+        },
         IM::Swap { .. } => (),
+        // The message is drawn with the chat, by draw_chat
+        IM::Chat { .. } => (),
     }
 
-    match &st.ui_alert {
+    match &view.ui_alert {
         Some(state::UIAlert::Roll(v)) => {
             let x = (WINDOW_WIDTH - CARD_LARGE_DISPLAY_WIDTH - DICE_ROLL_MAT_SIDELEN) / 2;
             let y = (WINDOW_HEIGHT - DICE_ROLL_MAT_SIDELEN) / 2;
@@ -127,32 +160,381 @@ pub fn draw(
     Ok(())
 }
 
+/// The log panel, over where the focused card goes. Newest events are at
+/// the bottom unless scrolled back. Cards hidden from `viewer` aren't named.
+pub fn draw_log(
+    canvas: &mut Renderer,
+    log: &GameLog,
+    viewer: state::Player,
+    view: &LogView,
+    art: &CardArt,
+) -> Result<(), String>
+{
+    let scale = LOG_TEXT_SCALE;
+    let line_height = (pixel_font::LINE_ADVANCE * scale) as i32;
+    let x = (WINDOW_WIDTH - CARD_LARGE_DISPLAY_WIDTH) as i32;
+    canvas.set_draw_color(LOG_PANEL_COLOR);
+    canvas.fill_rect(rect!(x, 0, CARD_LARGE_DISPLAY_WIDTH, WINDOW_HEIGHT))?;
+
+    let text_x = x + LOG_PADDING;
+    let title = if view.scroll > 0 {
+        format!("Log ({} newer below)", view.scroll)
+    } else {
+        "Log".to_string()
+    };
+    pixel_font::draw_text(canvas, text_x, LOG_PADDING, scale, LOG_TITLE_COLOR, &title)?;
+
+    let top = LOG_PADDING + line_height * 2;
+    let text_width = CARD_LARGE_DISPLAY_WIDTH - 2 * LOG_PADDING as u32;
+    let max_chars = ((text_width / scale + 1) / pixel_font::GLYPH_ADVANCE) as usize;
+    let max_lines = ((WINDOW_HEIGHT as i32 - LOG_PADDING - top) / line_height) as usize;
+
+    // Work backwards from the newest event shown until the panel is full
+    let shown = log.len().saturating_sub(view.scroll);
+    let mut lines: Vec<String> = Vec::new();
+    for (i, event) in log.events()[..shown].iter().enumerate().rev() {
+        let event = view::event_seen_by(viewer, event);
+        let text = format!("{}. {}", i + 1, event.describe(art.indexer, art.db));
+        let wrapped = pixel_font::wrap(&text, max_chars);
+        if lines.len() + wrapped.len() > max_lines {
+            break;
+        }
+        lines.splice(0..0, wrapped);
+    }
+
+    for (i, line) in lines.iter().enumerate() {
+        let y = top + i as i32 * line_height;
+        pixel_font::draw_text(canvas, text_x, y, scale, LOG_TEXT_COLOR, line)?;
+    }
+    Ok(())
+}
+
+/// The newest chat messages and the one being typed, along the bottom of
+/// where the focused card goes. Nothing until someone says something.
+pub fn draw_chat(canvas: &mut Renderer, log: &GameLog, view: &PlayerView) -> Result<(), String>
+{
+    let typed = match &view.input_mode {
+        state::InputMode::Chat { typed, .. } => Some(typed),
+        _ => None,
+    };
+    if typed.is_none() && log.chat().next().is_none() {
+        return Ok(());
+    }
+
+    let scale = LOG_TEXT_SCALE;
+    let line_height = (pixel_font::LINE_ADVANCE * scale) as i32;
+    let x = (WINDOW_WIDTH - CARD_LARGE_DISPLAY_WIDTH) as i32;
+    let panel_y = (WINDOW_HEIGHT - CHAT_PANEL_HEIGHT) as i32;
+    canvas.set_draw_color(CHAT_PANEL_COLOR);
+    canvas.fill_rect(rect!(x, panel_y, CARD_LARGE_DISPLAY_WIDTH, CHAT_PANEL_HEIGHT))?;
+
+    let text_x = x + LOG_PADDING;
+    let text_width = CARD_LARGE_DISPLAY_WIDTH - 2 * LOG_PADDING as u32;
+    let max_chars = ((text_width / scale + 1) / pixel_font::GLYPH_ADVANCE) as usize;
+    let mut max_lines = ((CHAT_PANEL_HEIGHT as i32 - 2 * LOG_PADDING) / line_height) as usize;
+
+    // The message being typed goes at the bottom, with a cursor
+    let mut bottom = panel_y + CHAT_PANEL_HEIGHT as i32 - LOG_PADDING;
+    if let Some(typed) = typed {
+        let typing = pixel_font::wrap(&format!("> {}_", typed), max_chars);
+        let shown = &typing[typing.len().saturating_sub(max_lines)..];
+        for line in shown.iter().rev() {
+            bottom -= line_height;
+            pixel_font::draw_text(canvas, text_x, bottom, scale, CHAT_TYPING_COLOR, line)?;
+        }
+        max_lines -= shown.len();
+    }
+
+    // Work backwards from the newest message until the panel is full
+    let mut lines: Vec<(state::Player, String)> = Vec::new();
+    for (player, text) in log.chat().rev() {
+        let text = format!("P{}: {}", player_number(player), text);
+        let wrapped = pixel_font::wrap(&text, max_chars);
+        if lines.len() + wrapped.len() > max_lines {
+            break;
+        }
+        lines.splice(0..0, wrapped.into_iter().map(|line| (player, line)));
+    }
+    let top = bottom - lines.len() as i32 * line_height;
+    for (i, (player, line)) in lines.iter().enumerate() {
+        let y = top + i as i32 * line_height;
+        pixel_font::draw_text(canvas, text_x, y, scale, player_color(*player), line)?;
+    }
+    Ok(())
+}
+
+/// How many of each card are left in the deck, over where the focused card
+/// goes.
+fn draw_deck_summary(canvas: &mut Renderer, deck: &[usize], art: &CardArt) -> Result<(), String>
+{
+    let scale = LOG_TEXT_SCALE;
+    let line_height = (pixel_font::LINE_ADVANCE * scale) as i32;
+    let x = (WINDOW_WIDTH - CARD_LARGE_DISPLAY_WIDTH) as i32;
+    canvas.set_draw_color(LOG_PANEL_COLOR);
+    canvas.fill_rect(rect!(x, 0, CARD_LARGE_DISPLAY_WIDTH, WINDOW_HEIGHT))?;
+
+    let text_x = x + LOG_PADDING;
+    let title = format!("Deck: {} cards", deck.len());
+    pixel_font::draw_text(canvas, text_x, LOG_PADDING, scale, LOG_TITLE_COLOR, &title)?;
+
+    let text_width = CARD_LARGE_DISPLAY_WIDTH - 2 * LOG_PADDING as u32;
+    let max_chars = ((text_width / scale + 1) / pixel_font::GLYPH_ADVANCE) as usize;
+    let mut y = LOG_PADDING + line_height * 2;
+    for (name, count) in deck_export::count_by_name(deck, art.indexer, art.db) {
+        for line in pixel_font::wrap(&format!("{} {}", count, name), max_chars) {
+            pixel_font::draw_text(canvas, text_x, y, scale, LOG_TEXT_COLOR, &line)?;
+            y += line_height;
+        }
+    }
+    Ok(())
+}
+
+/// A line of text in a box at the top of the board, for things like where a
+/// replay is up to.
+pub fn draw_status(canvas: &mut Renderer, text: &str) -> Result<(), String>
+{
+    let scale = STATUS_TEXT_SCALE;
+    let width = pixel_font::text_width(text, scale) + 2 * LOG_PADDING as u32;
+    let height = pixel_font::GLYPH_HEIGHT * scale + 2 * LOG_PADDING as u32;
+    let board_width = WINDOW_WIDTH - CARD_LARGE_DISPLAY_WIDTH;
+    let x = (board_width.saturating_sub(width) / 2) as i32;
+    canvas.set_draw_color(STATUS_COLOR);
+    canvas.fill_rect(rect!(x, 0, width, height))?;
+    pixel_font::draw_text(canvas, x + LOG_PADDING, LOG_PADDING, scale, STATUS_TEXT_COLOR, text)
+}
+
+/// Shades the board with a message across the middle, for while the game
+/// is paused.
+pub fn draw_waiting(canvas: &mut Renderer, text: &str) -> Result<(), String>
+{
+    let board_width = WINDOW_WIDTH - CARD_LARGE_DISPLAY_WIDTH;
+    canvas.set_blend_mode(BlendMode::Blend);
+    canvas.set_draw_color(WAITING_SHADE_COLOR);
+    canvas.fill_rect(rect!(0, 0, board_width, WINDOW_HEIGHT))?;
+    canvas.set_blend_mode(BlendMode::None);
+
+    let scale = WAITING_TEXT_SCALE;
+    let line_height = pixel_font::LINE_ADVANCE * scale;
+    let text_width = board_width - 4 * LOG_PADDING as u32;
+    let max_chars = ((text_width / scale + 1) / pixel_font::GLYPH_ADVANCE) as usize;
+    let lines = pixel_font::wrap(text, max_chars);
+    let widest = lines.iter().map(|line| pixel_font::text_width(line, scale)).max();
+    let width = widest.unwrap_or(0) + 2 * LOG_PADDING as u32;
+    let height = lines.len() as u32 * line_height + 2 * LOG_PADDING as u32;
+    let x = ((board_width - width) / 2) as i32;
+    let y = ((WINDOW_HEIGHT - height) / 2) as i32;
+    canvas.set_draw_color(STATUS_COLOR);
+    canvas.fill_rect(rect!(x, y, width, height))?;
+    for (i, line) in lines.iter().enumerate() {
+        let line_y = y + LOG_PADDING + (i as u32 * line_height) as i32;
+        pixel_font::draw_text(canvas, x + LOG_PADDING, line_y, scale, STATUS_TEXT_COLOR, line)?;
+    }
+    Ok(())
+}
+
+fn player_color(player: state::Player) -> Color
+{
+    match player {
+        state::Player::Player1 => PLAYER1_SLEEVE_COLOR,
+        state::Player::Player2 => PLAYER2_SLEEVE_COLOR,
+    }
+}
+
+fn player_number(player: state::Player) -> u32
+{
+    match player {
+        state::Player::Player1 => 1,
+        state::Player::Player2 => 2,
+    }
+}
+
+/// The deck list on the left, grouped by directory, and the highlighted
+/// deck's cards and legality on the right.
+pub fn draw_deck_picker(
+    canvas: &mut Renderer,
+    picker: &DeckPicker,
+    textures: &mut TextureCache,
+) -> Result<(), String>
+{
+    let scale = PICKER_TEXT_SCALE;
+    let line_height = (pixel_font::LINE_ADVANCE * scale) as i32;
+    let mut y = PICKER_MARGIN;
+
+    let (title, title_color) = match picker.picking() {
+        Some(player) => {
+            let title = format!("Player {}: pick a deck", player_number(player));
+            (title, player_color(player))
+        },
+        None => ("Starting...".to_string(), PICKER_TEXT_COLOR),
+    };
+    pixel_font::draw_text(canvas, PICKER_MARGIN, y, scale, title_color, &title)?;
+    y += line_height * 2;
+
+    if picker.decks.is_empty() {
+        let message = "No decks found. Put some .txt deck lists in the decks directory.";
+        pixel_font::draw_text(canvas, PICKER_MARGIN, y, scale, PICKER_TEXT_COLOR, message)?;
+        return Ok(());
+    }
+
+    // One row per deck plus a header whenever the directory changes
+    let mut rows: Vec<(Option<usize>, String)> = Vec::new();
+    let mut current_group = None;
+    for (i, deck) in picker.decks.iter().enumerate() {
+        if current_group != Some(deck.group()) {
+            current_group = Some(deck.group());
+            if deck.group() != std::path::Path::new("") {
+                rows.push((None, format!("{}/", deck.group().display())));
+            }
+        }
+        let name = deck.path.file_stem().map(|s| s.to_string_lossy()).unwrap_or_default();
+        rows.push((Some(i), name.to_string()));
+    }
+
+    // Scroll so the highlighted deck stays on screen
+    let help_y = WINDOW_HEIGHT as i32 - PICKER_MARGIN - line_height;
+    let visible_rows = ((help_y - y) / line_height).max(1) as usize;
+    let highlighted_row = rows.iter().position(|(i, _)| *i == Some(picker.highlight)).unwrap_or(0);
+    let first_row = highlighted_row.saturating_sub(visible_rows / 2);
+    let max_chars = (PICKER_LIST_WIDTH / (pixel_font::GLYPH_ADVANCE * scale)) as usize;
+
+    for (deck, text) in rows.iter().skip(first_row).take(visible_rows) {
+        match deck {
+            None => {
+                pixel_font::draw_text(canvas, PICKER_MARGIN, y, scale, PICKER_GROUP_COLOR, text)?;
+            },
+            Some(i) => {
+                if *i == picker.highlight {
+                    canvas.set_draw_color(HIGHLIGHT_COLOR);
+                    canvas.fill_rect(rect!(
+                        PICKER_MARGIN,
+                        y - scale as i32,
+                        PICKER_LIST_WIDTH,
+                        line_height
+                    ))?;
+                }
+                let (text, color) = match picker.chosen_by(*i) {
+                    Some(player) => {
+                        (format!("{} (P{})", text, player_number(player)), player_color(player))
+                    },
+                    None => (text.clone(), PICKER_TEXT_COLOR),
+                };
+                let text: String = text.chars().take(max_chars.saturating_sub(2)).collect();
+                let x = PICKER_MARGIN + (2 * pixel_font::GLYPH_ADVANCE * scale) as i32;
+                pixel_font::draw_text(canvas, x, y, scale, color, &text)?;
+            },
+        }
+        y += line_height;
+    }
+
+    let help = "; and ' to browse, Enter to pick, Esc to go back";
+    pixel_font::draw_text(canvas, PICKER_MARGIN, help_y, scale, PICKER_GROUP_COLOR, help)?;
+
+    let Some(deck) = picker.decks.get(picker.highlight) else {
+        return Ok(());
+    };
+    let preview_x = PICKER_MARGIN + PICKER_LIST_WIDTH as i32 + PICKER_MARGIN;
+    let preview_width = WINDOW_WIDTH - preview_x as u32 - PICKER_MARGIN as u32;
+    let mut y = PICKER_MARGIN;
+    let deck_name = deck.path.display().to_string();
+    pixel_font::draw_text(canvas, preview_x, y, scale, PICKER_TEXT_COLOR, &deck_name)?;
+    y += line_height * 2;
+
+    match &deck.preview {
+        DeckPreview::NotLoaded => {
+            pixel_font::draw_text(canvas, preview_x, y, scale, PICKER_GROUP_COLOR, "Loading...")?;
+        },
+        DeckPreview::Broken(report) => {
+            for line in report.lines() {
+                y += pixel_font::draw_wrapped_text(
+                    canvas,
+                    preview_x,
+                    y,
+                    preview_width,
+                    2,
+                    PICKER_ILLEGAL_COLOR,
+                    line,
+                )? as i32;
+            }
+        },
+        DeckPreview::Loaded { cards, total, report } => {
+            let (status, color) = if report.is_legal() {
+                (format!("{} cards, {} legal", total, picker.rules.name), PICKER_LEGAL_COLOR)
+            } else {
+                (format!("{} cards, not {} legal", total, picker.rules.name), PICKER_ILLEGAL_COLOR)
+            };
+            pixel_font::draw_text(canvas, preview_x, y, scale, color, &status)?;
+            y += line_height;
+            for violation in report.violations.iter() {
+                let text = violation.to_string();
+                y += pixel_font::draw_wrapped_text(
+                    canvas,
+                    preview_x,
+                    y,
+                    preview_width,
+                    2,
+                    PICKER_ILLEGAL_COLOR,
+                    &text,
+                )? as i32;
+            }
+            if !report.unchecked.is_empty() {
+                let text = format!("{} cards have no metadata to check", report.unchecked.len());
+                y += pixel_font::draw_wrapped_text(
+                    canvas,
+                    preview_x,
+                    y,
+                    preview_width,
+                    2,
+                    PICKER_GROUP_COLOR,
+                    &text,
+                )? as i32;
+            }
+            y += line_height;
+
+            let mut art = CardArt { textures, indexer: &picker.indexer, db: &picker.db };
+            let cell_width = CARD_SMALL_DISPLAY_WIDTH + PICKER_THUMBNAIL_SPACING;
+            let cell_height = CARD_SMALL_DISPLAY_HEIGHT + PICKER_THUMBNAIL_SPACING * 3;
+            let per_row = (preview_width / cell_width).max(1) as usize;
+            for (i, (card, count)) in cards.iter().enumerate() {
+                let x = preview_x + ((i % per_row) as u32 * cell_width) as i32;
+                let card_y = y + ((i / per_row) as u32 * cell_height) as i32;
+                if card_y + cell_height as i32 > help_y {
+                    break;
+                }
+                draw_card(*card, x, card_y, canvas, &mut art)?;
+                let count_y = card_y + CARD_SMALL_DISPLAY_HEIGHT as i32 + scale as i32;
+                let count = format!("x{}", count);
+                pixel_font::draw_text(canvas, x, count_y, 2, PICKER_TEXT_COLOR, &count)?;
+            }
+        },
+    }
+
+    Ok(())
+}
+
 fn draw_layout(
     canvas: &mut Renderer,
-    layout: &state::CardLayout,
+    layout: &LayoutView,
     side: Side,
     sleeve_color: Color,
-    card_textures: &Vec<Texture>,
+    art: &mut CardArt,
 ) -> Result<(), String>
 {
     // DRAW HAND
     let hand_len = layout.hand.len();
     for (i, card) in layout.hand.iter().enumerate() {
         let (x, y) = hand_card_location(i, hand_len, side);
-        if let Side::Facing = side {
-            draw_card(*card, x, y, canvas, card_textures)?;
-        } else {
-            draw_flipped_card(x, y, sleeve_color, canvas)?;
+        match card {
+            Some(card) => draw_card(*card, x, y, canvas, art)?,
+            None => draw_flipped_card(x, y, sleeve_color, canvas)?,
         }
     }
 
     // DRAW PRIZES
     for (i, prize_card) in layout.prizes.iter().enumerate() {
         let (x, y) = prize_card_location(i, side);
-        if prize_card.is_face_up {
-            draw_card(prize_card.card, x, y, canvas, card_textures)?;
-        } else {
-            draw_flipped_card(x, y, sleeve_color, canvas)?;
+        match prize_card {
+            Some(card) => draw_card(*card, x, y, canvas, art)?,
+            None => draw_flipped_card(x, y, sleeve_color, canvas)?,
         }
     }
 
@@ -160,7 +542,7 @@ fn draw_layout(
     for (i, slot) in layout.slots.iter().enumerate() {
         for (j, card) in slot.cards.iter().enumerate().rev() {
             let (x, y) = slot_card_location(i, j, side);
-            draw_card(*card, x, y, canvas, card_textures)?;
+            draw_card(*card, x, y, canvas, art)?;
             if j == 0 {
                 draw_damage_counters(
                     x + SPACE_BETWEEN_DICE as i32,
@@ -180,7 +562,7 @@ fn draw_layout(
     let mut draw_face_up_pile = |x, y, pile: &Vec<state::Card>| {
         if pile.len() > 0 {
             let top_card = pile[pile.len() - 1];
-            draw_card(top_card, x, y, canvas, card_textures)
+            draw_card(top_card, x, y, canvas, art)
         } else {
             Ok(())
         }
@@ -204,7 +586,7 @@ fn draw_layout(
 fn draw_deck_and_discard_search(
     cards: &Vec<usize>,
     canvas: &mut Renderer,
-    card_textures: &Vec<Texture>,
+    art: &mut CardArt,
 ) -> Result<(), String>
 {
     let background_y = (WINDOW_HEIGHT - SEARCH_HIGHLIGHT_HEIGHT) / 2;
@@ -214,7 +596,7 @@ fn draw_deck_and_discard_search(
 
     for (i, card) in cards.iter().enumerate() {
         let (x, y) = deck_and_discard_card_location(i, cards.len());
-        draw_card(*card, x, y, canvas, card_textures)?;
+        draw_card(*card, x, y, canvas, art)?;
     }
 
     Ok(())
@@ -225,27 +607,112 @@ fn draw_card(
     x: i32,
     y: i32,
     canvas: &mut Renderer,
-    card_textures: &Vec<Texture>,
+    art: &mut CardArt,
 ) -> Result<(), String>
 {
-    let tex = &card_textures[card];
-    //let (src_w, src_h) = card_indexer.get_dimensions(card);
     let (dst_w, dst_h) = CardDisplaySize::Small.dims();
-    canvas.copy(tex, None /*rect!(0, 0, src_w, src_h)*/, rect!(x, y, dst_w, dst_h))
+    match art.textures.get(card, art.indexer) {
+        Some(tex) => draw_letterboxed(tex, rect!(x, y, dst_w, dst_h), canvas),
+        None => draw_placeholder_card(card, rect!(x, y, dst_w, dst_h), 1, canvas, art),
+    }
 }
 
 fn draw_focused_card(
     card: usize,
     canvas: &mut Renderer,
-    card_textures: &Vec<Texture>,
+    art: &mut CardArt,
 ) -> Result<(), String>
 {
-    let tex = &card_textures[card];
     let x = WINDOW_WIDTH - CARD_LARGE_DISPLAY_WIDTH;
     let y = 0;
-    //let (src_w, src_h) = card_indexer.get_dimensions(card);
     let (dst_w, dst_h) = CardDisplaySize::Large.dims();
-    canvas.copy(tex, None /*rect!(0, 0, src_w, src_h)*/, rect!(x, y, dst_w, dst_h))
+    match art.textures.get(card, art.indexer) {
+        Some(tex) => draw_letterboxed(tex, rect!(x, y, dst_w, dst_h), canvas),
+        None => draw_placeholder_card(card, rect!(x, y, dst_w, dst_h), 6, canvas, art),
+    }
+}
+
+/// Stands in for cards without an image (e.g. proxies for a set that hasn't
+/// been scanned yet): a frame colored by supertype, the name and the set.
+fn draw_placeholder_card(
+    card: usize,
+    dst: Rect,
+    text_scale: u32,
+    canvas: &mut Renderer,
+    art: &CardArt,
+) -> Result<(), String>
+{
+    let (set, card_id) = art.indexer.identity(card);
+    let info = art.db.get(card);
+    let frame_color = match info.map(|info| info.supertype) {
+        Some(Supertype::Pokemon) => PLACEHOLDER_POKEMON_COLOR,
+        Some(Supertype::Trainer) => PLACEHOLDER_TRAINER_COLOR,
+        Some(Supertype::Energy) => PLACEHOLDER_ENERGY_COLOR,
+        None => PLACEHOLDER_UNKNOWN_COLOR,
+    };
+    canvas.set_draw_color(frame_color);
+    canvas.fill_rect(dst)?;
+
+    let border = PLACEHOLDER_BORDER * text_scale;
+    let inner = rect!(
+        dst.x() + border as i32,
+        dst.y() + border as i32,
+        dst.width().saturating_sub(2 * border),
+        dst.height().saturating_sub(2 * border)
+    );
+    canvas.set_draw_color(PLACEHOLDER_INNER_COLOR);
+    canvas.fill_rect(inner)?;
+
+    let name = match info {
+        Some(info) => info.name.clone(),
+        None => card_id.replace('-', " "),
+    };
+    let set_text = match info.and_then(|info| info.set.as_ref().zip(info.number.as_ref())) {
+        Some((set_code, number)) => format!("{} {}", set_code, number),
+        None => set.to_string(),
+    };
+
+    let padding = text_scale as i32;
+    let text_x = inner.x() + padding;
+    let text_width = inner.width().saturating_sub(2 * padding as u32);
+    pixel_font::draw_wrapped_text(
+        canvas,
+        text_x,
+        inner.y() + padding,
+        text_width,
+        text_scale,
+        PLACEHOLDER_TEXT_COLOR,
+        &name,
+    )?;
+    let set_y = inner.bottom() - padding - (pixel_font::GLYPH_HEIGHT * text_scale) as i32;
+    let max_chars = (text_width / (pixel_font::GLYPH_ADVANCE * text_scale)) as usize;
+    let set_text: String = set_text.chars().take(max_chars).collect();
+    pixel_font::draw_text(canvas, text_x, set_y, text_scale, PLACEHOLDER_TEXT_COLOR, &set_text)
+}
+
+/// Fits the whole image inside `dst` without stretching it, filling the
+/// leftover space so cards from differently-proportioned sets line up.
+fn draw_letterboxed(tex: &Texture, dst: Rect, canvas: &mut Renderer) -> Result<(), String>
+{
+    let query = tex.query();
+    let (src_w, src_h) = (query.width, query.height);
+    if src_w == 0 || src_h == 0 {
+        return Ok(());
+    }
+    // Compare src_w / src_h against dst_w / dst_h without dividing
+    let src_is_wider = src_w as u64 * dst.height() as u64 > dst.width() as u64 * src_h as u64;
+    let (w, h) = if src_is_wider {
+        (dst.width(), (dst.width() as u64 * src_h as u64 / src_w as u64) as u32)
+    } else {
+        ((dst.height() as u64 * src_w as u64 / src_h as u64) as u32, dst.height())
+    };
+    if (w, h) != (dst.width(), dst.height()) {
+        canvas.set_draw_color(LETTERBOX_COLOR);
+        canvas.fill_rect(dst)?;
+    }
+    let x = dst.x() + (dst.width() - w) as i32 / 2;
+    let y = dst.y() + (dst.height() - h) as i32 / 2;
+    canvas.copy(tex, rect!(0, 0, src_w, src_h), rect!(x, y, w, h))
 }
 
 fn draw_flipped_card(
@@ -288,23 +755,24 @@ fn highlight_card_at(x: i32, y: i32, color: Color, canvas: &mut Renderer) -> Res
 fn highlight_selection(
     selection: &state::Selection,
     color: Color,
-    layout: &state::CardLayout,
+    view: &PlayerView,
     canvas: &mut Renderer,
 ) -> Result<(), String>
 {
     use state::Selection as S;
-    let side = Side::Facing;
+    let layout = view.layout(selection.player());
+    let side = if selection.player() == view.viewer { Side::Facing } else { Side::Opposing };
     let (x, y) = match selection {
-        S::Hand { index } => hand_card_location(*index, layout.hand.len(), side),
-        S::Prize { index } => prize_card_location(*index, side),
-        S::Slot { slot_index, pokemon_index } => {
+        S::Hand { index, .. } => hand_card_location(*index, layout.hand.len(), side),
+        S::Prize { index, .. } => prize_card_location(*index, side),
+        S::Slot { slot_index, pokemon_index, .. } => {
             let pokemon_index = match pokemon_index {
                 Some(i) => *i,
                 None => 0,
             };
             slot_card_location(*slot_index, pokemon_index, side)
         },
-        S::Discard { index } => deck_and_discard_card_location(*index, layout.discard.len()),
+        S::Discard { index, .. } => deck_and_discard_card_location(*index, layout.discard.len()),
         S::LostZone { .. } => lost_zone_location(side),
         S::Stadium { .. } => stadium_location(side),
     };
@@ -0,0 +1,254 @@
+//! What happened in a game, one `GameEvent` per change, for reviewing lines
+//! of play afterwards. The log only ever grows: undoing adds an `Undo` entry
+//! rather than removing the undone events. Chat messages go in the log too,
+//! so what the players said stays with what they did.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::card_db::CardDb;
+use crate::load_cards::CardIndexer;
+use crate::state::{Card, Player};
+use crate::view;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Zone
+{
+    Hand,
+    Deck,
+    Discard,
+    LostZone,
+    Prizes,
+    Stadium,
+    /// 0 is the Active spot.
+    Slot(usize),
+}
+
+impl fmt::Display for Zone
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self {
+            Zone::Hand => write!(f, "hand"),
+            Zone::Deck => write!(f, "deck"),
+            Zone::Discard => write!(f, "discard"),
+            Zone::LostZone => write!(f, "Lost Zone"),
+            Zone::Prizes => write!(f, "prizes"),
+            Zone::Stadium => write!(f, "stadium"),
+            Zone::Slot(0) => write!(f, "Active"),
+            Zone::Slot(slot) => write!(f, "bench {}", slot),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameEvent
+{
+    /// `player` is whose side the card left, `to_player` whose it went to.
+    /// `card` is None when it's hidden from whoever the event was sent to.
+    CardMoved
+    {
+        player: Player,
+        card: Option<Card>,
+        from: Zone,
+        from_index: usize,
+        to_player: Player,
+        to: Zone,
+        to_index: usize,
+    },
+    DamageChanged
+    {
+        player: Player, slot: usize, from: u8, to: u8
+    },
+    PrizeFlipped
+    {
+        player: Player, index: usize, face_up: bool
+    },
+    SlotsSwapped
+    {
+        player: Player, first: usize, second: usize
+    },
+    DeckShuffled
+    {
+        player: Player
+    },
+    DieRolled
+    {
+        player: Player, value: u8
+    },
+    SidesSwitched
+    {
+        to: Player
+    },
+    Chat
+    {
+        player: Player, text: String
+    },
+    Undo,
+    Redo,
+}
+
+fn card_name(card: Card, indexer: &CardIndexer, db: &CardDb) -> String
+{
+    match db.name(card) {
+        Some(name) => name.to_string(),
+        None => indexer.identity(card).1.to_string(),
+    }
+}
+
+fn slot_name(slot: usize) -> String
+{
+    Zone::Slot(slot).to_string()
+}
+
+impl GameEvent
+{
+    /// One line of text, naming cards rather than giving their indices.
+    pub fn describe(&self, indexer: &CardIndexer, db: &CardDb) -> String
+    {
+        use GameEvent as E;
+        match self {
+            E::CardMoved { player, card, from, from_index, to_player, to, to_index } => {
+                let to = if to_player == player {
+                    to.to_string()
+                } else {
+                    format!("{}'s {}", to_player, to)
+                };
+                format!(
+                    "{}: {} from {} ({}) to {} ({})",
+                    player,
+                    card.map_or("a card".to_string(), |card| card_name(card, indexer, db)),
+                    from,
+                    from_index + 1,
+                    to,
+                    to_index + 1
+                )
+            },
+            E::DamageChanged { player, slot, from, to } => {
+                format!("{}: damage counters on {} {} -> {}", player, slot_name(*slot), from, to)
+            },
+            E::PrizeFlipped { player, index, face_up } => {
+                let side = if *face_up { "up" } else { "down" };
+                format!("{}: prize {} flipped face {}", player, index + 1, side)
+            },
+            E::SlotsSwapped { player, first, second } => {
+                format!("{}: swapped {} and {}", player, slot_name(*first), slot_name(*second))
+            },
+            E::DeckShuffled { player } => format!("{}: shuffled deck", player),
+            E::DieRolled { player, value } => format!("{}: rolled a {}", player, value),
+            E::SidesSwitched { to } => format!("Switched to {}'s side", to),
+            E::Chat { player, text } => format!("{} said: {}", player, text),
+            E::Undo => "Undo".to_string(),
+            E::Redo => "Redo".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct GameLog
+{
+    events: Vec<GameEvent>,
+}
+
+impl GameLog
+{
+    pub fn push(&mut self, event: GameEvent)
+    {
+        self.events.push(event);
+    }
+
+    pub fn extend<I: IntoIterator<Item = GameEvent>>(&mut self, events: I)
+    {
+        self.events.extend(events);
+    }
+
+    pub fn events(&self) -> &[GameEvent]
+    {
+        &self.events
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.events.is_empty()
+    }
+
+    /// Chat messages, oldest first.
+    pub fn chat(&self) -> impl DoubleEndedIterator<Item = (Player, &str)>
+    {
+        self.events.iter().filter_map(|event| match event {
+            GameEvent::Chat { player, text } => Some((*player, text.as_str())),
+            _ => None,
+        })
+    }
+
+    /// Numbered lines, one per event, naming only the cards `viewer` could
+    /// see.
+    pub fn to_text(&self, viewer: Player, indexer: &CardIndexer, db: &CardDb) -> String
+    {
+        let mut text = String::new();
+        for (i, event) in self.events.iter().enumerate() {
+            let event = view::event_seen_by(viewer, event);
+            text.push_str(&format!("{}. {}\n", i + 1, event.describe(indexer, db)));
+        }
+        text
+    }
+}
+
+/// Whether the log panel is showing and how far it's scrolled back.
+#[derive(Clone, Debug, Default)]
+pub struct LogView
+{
+    pub visible: bool,
+    /// Events hidden below the bottom of the panel; 0 follows the newest.
+    pub scroll: usize,
+}
+
+impl LogView
+{
+    pub fn scroll_up(&mut self, lines: usize, log: &GameLog)
+    {
+        self.scroll = (self.scroll + lines).min(log.len().saturating_sub(1));
+    }
+
+    pub fn scroll_down(&mut self, lines: usize)
+    {
+        self.scroll = self.scroll.saturating_sub(lines);
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn text_only_names_cards_the_viewer_could_see()
+    {
+        let mut indexer = CardIndexer::default();
+        let card = indexer.intern("mt", "pachirisu-mt-86");
+        let mut log = GameLog::default();
+        log.push(GameEvent::CardMoved {
+            player: Player::Player1,
+            card: Some(card),
+            from: Zone::Deck,
+            from_index: 9,
+            to_player: Player::Player1,
+            to: Zone::Hand,
+            to_index: 0,
+        });
+        let db = CardDb::default();
+        assert_eq!(
+            log.to_text(Player::Player1, &indexer, &db),
+            "1. Player 1: pachirisu-mt-86 from deck (10) to hand (1)\n"
+        );
+        assert_eq!(
+            log.to_text(Player::Player2, &indexer, &db),
+            "1. Player 1: a card from deck (10) to hand (1)\n"
+        );
+    }
+}
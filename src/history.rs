@@ -0,0 +1,189 @@
+//! Undo/redo for anything that changes the board. Each entry is a whole
+//! `State` from before the change, which is cheap enough at a few hundred
+//! small vectors a game. Undoing goes back to just before the change, so
+//! undoing a Move puts you back at choosing where the cards go.
+//!
+//! Random events keep whatever result they had: undoing a shuffle puts the
+//! deck back in its old order, and redoing it gives the same order as before
+//! rather than shuffling again. Since the `State`'s RNG is restored along
+//! with everything else, shuffling again straight after an undo gives that
+//! same order too. Die rolls use the same RNG without changing the board, so
+//! they aren't recorded at all (there's no taking back a roll), but rolling
+//! in between does change what the next shuffle gives.
+
+use std::collections::VecDeque;
+
+use crate::game_log::GameEvent;
+use crate::input::Input;
+use crate::state::State;
+
+pub const DEFAULT_UNDO_DEPTH: usize = 200;
+
+pub struct History
+{
+    // Oldest first
+    undo_stack: VecDeque<State>,
+    redo_stack: Vec<State>,
+    depth: usize,
+}
+
+impl History
+{
+    /// Keeps at most `depth` states to undo to; 0 turns undo off.
+    pub fn new(depth: usize) -> Self
+    {
+        History { undo_stack: VecDeque::new(), redo_stack: Vec::new(), depth }
+    }
+
+    /// `State::update`, remembering the previous state if the board changed.
+    pub fn update(&mut self, st: &mut State, input: &Input) -> Vec<GameEvent>
+    {
+        let before = st.clone();
+        let events = st.update(input);
+        if before.player1_layout != st.player1_layout || before.player2_layout != st.player2_layout
+        {
+            self.record(before);
+        }
+        events
+    }
+
+    /// For changes made some other way than `update`, like loading a save.
+    pub fn record(&mut self, before: State)
+    {
+        self.push_undo(before);
+        // A new change means the undone ones can't be redone any more
+        self.redo_stack.clear();
+    }
+
+    /// Returns false if there was nothing to undo.
+    pub fn undo(&mut self, st: &mut State) -> bool
+    {
+        match self.undo_stack.pop_back() {
+            Some(previous) => {
+                let current = std::mem::replace(st, previous);
+                self.redo_stack.push(current);
+                st.ui_alert = None;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Returns false if there was nothing to redo.
+    pub fn redo(&mut self, st: &mut State) -> bool
+    {
+        match self.redo_stack.pop() {
+            Some(next) => {
+                let current = std::mem::replace(st, next);
+                self.push_undo(current);
+                st.ui_alert = None;
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn can_undo(&self) -> bool
+    {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool
+    {
+        !self.redo_stack.is_empty()
+    }
+
+    fn push_undo(&mut self, st: State)
+    {
+        if self.depth == 0 {
+            return;
+        }
+        if self.undo_stack.len() == self.depth {
+            self.undo_stack.pop_front();
+        }
+        self.undo_stack.push_back(st);
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    /// A deck of 1 to 5 with 5 on top, being drawn from.
+    fn drawing() -> State
+    {
+        let mut st = State::make(vec![1, 2, 3, 4, 5], Vec::new(), 0);
+        st.update(&Input::Deck);
+        st
+    }
+
+    /// Draws `count` cards through `history`.
+    fn draw(history: &mut History, st: &mut State, count: usize)
+    {
+        for _ in 0..count {
+            history.update(st, &Input::Deck);
+        }
+    }
+
+    #[test]
+    fn undo_and_redo_go_back_and_forth()
+    {
+        let mut st = drawing();
+        let mut history = History::new(DEFAULT_UNDO_DEPTH);
+        assert!(!history.undo(&mut st));
+        draw(&mut history, &mut st, 2);
+        assert_eq!(st.player1_layout.hand, vec![5, 4]);
+
+        assert!(history.undo(&mut st));
+        assert_eq!(st.player1_layout.hand, vec![5]);
+        assert!(history.undo(&mut st));
+        assert!(st.player1_layout.hand.is_empty());
+        assert!(!history.can_undo());
+
+        assert!(history.redo(&mut st));
+        assert_eq!(st.player1_layout.hand, vec![5]);
+        assert!(history.can_undo() && history.can_redo());
+
+        // Drawing again takes the place of the draw that was undone
+        draw(&mut history, &mut st, 1);
+        assert!(!history.can_redo());
+        assert!(!history.redo(&mut st));
+        assert_eq!(st.player1_layout.hand, vec![5, 4]);
+    }
+
+    #[test]
+    fn only_board_changes_are_recorded()
+    {
+        let mut st = drawing();
+        let mut history = History::new(DEFAULT_UNDO_DEPTH);
+        for input in [Input::Cancel, Input::Roll, Input::Hand, Input::Deck, Input::Look] {
+            history.update(&mut st, &input);
+        }
+        assert!(!history.can_undo());
+
+        history.record(st.clone());
+        assert!(history.can_undo());
+    }
+
+    #[test]
+    fn only_the_last_few_changes_can_be_undone()
+    {
+        let mut st = drawing();
+        let mut history = History::new(2);
+        draw(&mut history, &mut st, 4);
+        assert!(history.undo(&mut st));
+        assert!(history.undo(&mut st));
+        assert!(!history.undo(&mut st));
+        assert_eq!(st.player1_layout.hand, vec![5, 4]);
+
+        // Both can still be redone
+        assert!(history.redo(&mut st));
+        assert!(history.redo(&mut st));
+        assert_eq!(st.player1_layout.hand, vec![5, 4, 3, 2]);
+
+        let mut history = History::new(0);
+        draw(&mut history, &mut st, 1);
+        assert!(!history.can_undo());
+    }
+}
@@ -1,5 +1,7 @@
 use sdl2::keyboard::Keycode;
+use serde::{Deserialize, Serialize};
 
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Input
 {
     Left,
@@ -25,6 +27,7 @@ pub enum Input
     Decrement,
 
     SwitchSides,
+    OtherBoard,
 
     Move,
     Swap,
@@ -33,9 +36,30 @@ pub enum Input
     Prepend,
 
     Observe,
+    Look,
     Shuffle,
 
     Roll,
+
+    Chat,
+    /// Text typed into a chat message.
+    Type (String),
+    Backspace,
+    /// One of `state::EMOTES`.
+    Emote (usize),
+
+    ExportDeck,
+
+    Undo,
+    Redo,
+
+    ToggleLog,
+    ScrollLogUp,
+    ScrollLogDown,
+    ExportLog,
+
+    SaveGame,
+    LoadGame,
 }
 
 pub fn keycode_to_input(k: Keycode) -> Option<Input>
@@ -70,6 +94,7 @@ pub fn keycode_to_input(k: Keycode) -> Option<Input>
         K::Minus => I::Decrement,
 
         K::Space => I::SwitchSides,
+        K::V => I::OtherBoard,
 
         K::M => I::Move,
         K::W => I::Swap,
@@ -78,12 +103,94 @@ pub fn keycode_to_input(k: Keycode) -> Option<Input>
         K::E => I::Prepend,
 
         K::O => I::Observe,
+        K::K => I::Look,
 
         // TODO find a better letter than Q for shuffle??? Lol
         K::Q => I::Shuffle,
 
         K::R => I::Roll,
 
+        K::C => I::Chat,
+        K::F6 => I::Emote(0),
+        K::F7 => I::Emote(1),
+        K::F8 => I::Emote(2),
+        K::F10 => I::Emote(3),
+        K::F11 => I::Emote(4),
+        K::F12 => I::Emote(5),
+
+        K::F4 => I::ExportDeck,
+
+        K::Z => I::Undo,
+        K::Y => I::Redo,
+
+        K::Tab => I::ToggleLog,
+        K::PageUp => I::ScrollLogUp,
+        K::PageDown => I::ScrollLogDown,
+        K::F2 => I::ExportLog,
+
+        K::F5 => I::SaveGame,
+        K::F9 => I::LoadGame,
+
+        _ => return None,
+    };
+    Some(i)
+}
+
+/// Keys while typing a chat message. Letters come through as text instead,
+/// so they don't act as shortcuts.
+pub fn keycode_to_chat_input(k: Keycode) -> Option<Input>
+{
+    use Keycode as K;
+    use Input as I;
+    let i = match k {
+        K::Return | K::KpEnter => I::Select,
+        K::Escape => I::Cancel,
+        K::Backspace => I::Backspace,
+        _ => return None,
+    };
+    Some(i)
+}
+
+/// Keys for the replay viewer, which doesn't take game inputs.
+pub enum ReplayInput
+{
+    StepBack,
+    StepForward,
+    PreviousTurn,
+    NextTurn,
+    Start,
+    End,
+    /// Typing a turn number to jump to.
+    Digit(usize),
+    JumpToTurn,
+    ClearTyped,
+    ToggleLog,
+}
+
+pub fn keycode_to_replay_input(k: Keycode) -> Option<ReplayInput>
+{
+    use Keycode as K;
+    use ReplayInput as R;
+    let i = match k {
+        K::Semicolon | K::Left => R::StepBack,
+        K::Quote | K::Right => R::StepForward,
+        K::PageUp | K::Up => R::PreviousTurn,
+        K::PageDown | K::Down => R::NextTurn,
+        K::Home => R::Start,
+        K::End => R::End,
+        K::Num0 => R::Digit(0),
+        K::Num1 => R::Digit(1),
+        K::Num2 => R::Digit(2),
+        K::Num3 => R::Digit(3),
+        K::Num4 => R::Digit(4),
+        K::Num5 => R::Digit(5),
+        K::Num6 => R::Digit(6),
+        K::Num7 => R::Digit(7),
+        K::Num8 => R::Digit(8),
+        K::Num9 => R::Digit(9),
+        K::Return => R::JumpToTurn,
+        K::Escape | K::Backspace => R::ClearTyped,
+        K::Tab => R::ToggleLog,
         _ => return None,
     };
     Some(i)
@@ -1,5 +1,22 @@
+pub mod card_db;
+pub mod cli;
+pub mod config;
+pub mod deck_export;
+pub mod deck_import;
+pub mod deck_list;
+pub mod deck_picker;
 pub mod display_constants;
 pub mod state;
 pub mod draw_board;
+pub mod game_log;
+pub mod history;
 pub mod input;
 pub mod load_cards;
+pub mod net;
+pub mod pixel_font;
+pub mod replay;
+pub mod save;
+pub mod spectate;
+pub mod texture_cache;
+pub mod validation;
+pub mod view;
@@ -1,104 +1,226 @@
-use sdl2::image::LoadTexture;
-use sdl2::video::WindowContext;
-use sdl2::render::Texture;
-use sdl2::render::TextureCreator;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
+use std::fmt;
 use std::fs::read_to_string;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
+use crate::config::CardLibrary;
+use crate::deck_import::{self, SetTable};
+use crate::deck_list::{DeckList, DeckListError};
+
+#[derive(Clone, Debug)]
+pub enum DeckLoadError
+{
+    MissingFile
+    {
+        path: PathBuf, reason: String
+    },
+    MalformedLine
+    {
+        path: PathBuf, line: usize, content: String
+    },
+    BadCount
+    {
+        path: PathBuf, line: usize, count: String
+    },
+    UnknownCard
+    {
+        path: PathBuf, line: usize, card: String
+    },
+    MissingImage
+    {
+        path: PathBuf, reason: String
+    },
+    Unresolved
+    {
+        path: PathBuf, line: usize, text: String, reason: String
+    },
+}
+
+impl fmt::Display for DeckLoadError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        use DeckLoadError as E;
+        match self {
+            E::MissingFile { path, reason } => {
+                write!(f, "{}: couldn't read deck file ({})", path.display(), reason)
+            },
+            E::MalformedLine { path, line, content } => {
+                write!(f, "{}:{}: malformed line {:?}", path.display(), line, content)
+            },
+            E::BadCount { path, line, count } => {
+                write!(f, "{}:{}: {:?} isn't a card count", path.display(), line, count)
+            },
+            E::UnknownCard { path, line, card } => {
+                write!(f, "{}:{}: unknown card {:?}", path.display(), line, card)
+            },
+            E::MissingImage { path, reason } => {
+                write!(f, "{}: couldn't load card image ({})", path.display(), reason)
+            },
+            E::Unresolved { path, line, text, reason } => {
+                write!(f, "{}:{}: couldn't import {:?}: {}", path.display(), line, text, reason)
+            },
+        }
+    }
+}
+
+/// One problem per line, for showing the user everything that's wrong at once.
+pub fn error_report(errors: &[DeckLoadError]) -> String
+{
+    let mut report = format!("Couldn't load decks ({} problems):", errors.len());
+    for error in errors {
+        report.push_str("\n  ");
+        report.push_str(&error.to_string());
+    }
+    report
+}
+
+impl DeckLoadError
+{
+    fn from_list_error(path: &Path, error: DeckListError) -> Self
+    {
+        let path = path.to_path_buf();
+        match error {
+            DeckListError::MalformedLine { line, content } => {
+                DeckLoadError::MalformedLine { path, line, content }
+            },
+            DeckListError::BadCount { line, count } => {
+                DeckLoadError::BadCount { path, line, count }
+            },
+        }
+    }
+}
+
+/// Reads a deck in either the native format or the PTCG Live/PTCGO export format.
+pub fn load_deck_list(
+    library: &CardLibrary,
+    sets: &SetTable,
+    path: &Path,
+) -> Result<DeckList, Vec<DeckLoadError>>
+{
+    let deck_string = read_to_string(path).map_err(|e| {
+        vec![DeckLoadError::MissingFile { path: path.to_path_buf(), reason: e.to_string() }]
+    })?;
+    if deck_import::looks_like_export(&deck_string) {
+        let imported = deck_import::import_deck(library, sets, &deck_string);
+        if !imported.unresolved.is_empty() {
+            return Err(imported
+                .unresolved
+                .into_iter()
+                .map(|card| DeckLoadError::Unresolved {
+                    path: path.to_path_buf(),
+                    line: card.line,
+                    text: card.text,
+                    reason: card.reason,
+                })
+                .collect());
+        }
+        return Ok(imported.deck);
+    }
+    DeckList::parse(&deck_string).map_err(|errors| {
+        errors.into_iter().map(|e| DeckLoadError::from_list_error(path, e)).collect()
+    })
+}
+
+/// Gives every distinct card a `Card` index. Cards can be added at any time;
+/// their images are loaded separately by the `TextureCache`.
+#[derive(Clone, Debug, Default)]
 pub struct CardIndexer
 {
     name_to_index: HashMap<String, usize>,
-    //textures: Vec<Texture<'a>>,
-    dimensions: Vec<(u32, u32)>,
+    // (set, card id) for each index
+    identities: Vec<(String, String)>,
 }
 
-impl<'a> CardIndexer
+impl CardIndexer
 {
-    pub fn make(
-        deck1_filename: &str, deck2_filename: &str,
-
-        tex_creator: &'a TextureCreator<WindowContext>,
-        ) -> (Self, Vec<Texture<'a>>)
-    {
-        let cards_path = Path::new("/Users/mia/fun/mon_tcg_sim/cards");
-        let deck1_path = cards_path.join("decks").join(deck1_filename);
-        let deck1_string = match read_to_string(&deck1_path) {
-            Err(_) => panic!("Problem reading file {:?}", deck1_path),
-            Ok(s) => s,
-        };
-        let deck2_path = cards_path.join("decks").join(deck2_filename);
-        let deck2_string = match read_to_string(&deck2_path) {
-            Err(_) => panic!("Problem reading file {:?}", deck2_path),
-            Ok(s) => s,
-        };
-
-        let (sets_v, cards_v) : (Vec<(usize, &str)>, Vec<(usize, &str)>) = deck1_string
-            .lines()
-            .chain(deck2_string.lines())
-            .filter(|s| s.len() > 2)
-            .enumerate()
-            .partition(|(i, _)| i % 2 == 0);
-        let mut cards_set: HashSet<(String, String)> = HashSet::new();
-        for i in 0..sets_v.len() {
-            let set = sets_v[i].1.to_string();
-            let card = cards_v[i].1.to_string();
-            cards_set.insert((set, card));
+    pub fn make(decks: &[&DeckList]) -> Self
+    {
+        let mut indexer = CardIndexer::default();
+        for deck in decks {
+            for (set, card) in deck.distinct_cards() {
+                indexer.intern(set, card);
+            }
         }
+        indexer
+    }
+
+    /// Cards without an image. These still work, they're just drawn as
+    /// placeholders.
+    pub fn missing_images(&self, library: &CardLibrary) -> Vec<DeckLoadError>
+    {
+        self.identities
+            .iter()
+            .map(|(set, card)| library.image_path(set, card))
+            .filter(|path| !path.exists())
+            .map(|path| DeckLoadError::MissingImage {
+                path,
+                reason: "file doesn't exist".to_string(),
+            })
+            .collect()
+    }
 
-        let mut name_to_index = HashMap::new();
-        let mut textures = Vec::new();
-        let mut dimensions = Vec::new();
-
-        for (set, card) in cards_set.into_iter() {
-            let dims = dimensions_from_set(&set);
-            let card_path = cards_path.join(set).join(&card).with_extension("jpg");
-            let tex = tex_creator.load_texture(card_path).unwrap();
-            name_to_index.insert(card, textures.len());
-            textures.push(tex);
-            dimensions.push(dims);
+    /// The index for a card, adding it if it's new.
+    pub fn intern(&mut self, set: &str, card: &str) -> usize
+    {
+        if let Some(index) = self.index_of(card) {
+            return index;
         }
+        let index = self.identities.len();
+        self.name_to_index.insert(card.to_string(), index);
+        self.identities.push((set.to_string(), card.to_string()));
+        index
+    }
+
+    pub fn index_of(&self, card_name: &str) -> Option<usize>
+    {
+        self.name_to_index.get(card_name).copied()
+    }
 
-        (CardIndexer { name_to_index, dimensions }, textures)
+    pub fn len(&self) -> usize
+    {
+        self.identities.len()
     }
 
-    pub fn index_of(&self, card_name: &str) -> usize
+    pub fn is_empty(&self) -> bool
     {
-        self.name_to_index[card_name]
+        self.identities.is_empty()
     }
 
-    pub fn get_dimensions(&self, u: usize) -> (u32, u32) {
-        self.dimensions[u]
+    /// (set, card id) of a card, the same pair a deck list entry has.
+    pub fn identity(&self, u: usize) -> (&str, &str)
+    {
+        let (set, card) = &self.identities[u];
+        (set, card)
     }
 
-    pub fn build_deck(&self, deck_filename: &str) -> Vec<usize>
-    {
-        let cards_path = Path::new("/Users/mia/fun/mon_tcg_sim/cards");
-        let deck_path = cards_path.join("decks").join(deck_filename);
-        let deck_string = match read_to_string(&deck_path) {
-            Err(_) => panic!("Problem reading file {:?}", deck_path),
-            Ok(s) => s,
-        };
-        let (counts, cards) : (Vec<(usize, &str)>, Vec<(usize, &str)>) = deck_string
-            .lines()
-            .enumerate()
-            .filter(|(i, s)| !s.is_empty() && i % 3 != 1)
-            .partition(|(i, _)| i % 3 == 0);
+    /// `deck_path` is only used for error messages.
+    pub fn build_deck(
+        &self,
+        deck_path: &Path,
+        deck_list: &DeckList,
+    ) -> Result<Vec<usize>, Vec<DeckLoadError>>
+    {
+        let mut errors = Vec::new();
         let mut deck = Vec::new();
-        for i in 0..cards.len() {
-            let count = counts[i].1.parse::<u32>().unwrap();
-            let card = cards[i].1;
-            let card_index = self.index_of(card);
-            for _ in 0..count {
-                deck.push(card_index);
+        for entry in deck_list.entries.iter() {
+            match self.index_of(&entry.card) {
+                Some(card_index) => {
+                    for _ in 0..entry.count {
+                        deck.push(card_index);
+                    }
+                },
+                None => errors.push(DeckLoadError::UnknownCard {
+                    path: deck_path.to_path_buf(),
+                    line: entry.line,
+                    card: entry.card.clone(),
+                }),
             }
         }
-        deck
-    }
-}
 
-fn dimensions_from_set(set: &str) -> (u32, u32)
-{
-    // TODO!!!!
-    (600, 835)
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(deck)
+    }
 }
@@ -1,59 +1,715 @@
+use tcgclient::card_db::CardDb;
+use tcgclient::cli::{self, Cli, Command, PlayOptions};
+use tcgclient::config::CardLibrary;
+use tcgclient::deck_export;
+use tcgclient::deck_import::SetTable;
+use tcgclient::deck_picker::DeckPicker;
 use tcgclient::load_cards;
+use tcgclient::load_cards::DeckLoadError;
 use tcgclient::display_constants::*;
 use tcgclient::state;
 use tcgclient::input;
-use tcgclient::draw_board;
+use tcgclient::draw_board::{self, CardArt};
+use tcgclient::game_log::{GameLog, LogView};
+use tcgclient::history::History;
+use tcgclient::net::{self, Guest, Host};
+use tcgclient::replay::{self, Recorded, Recording, Replay, SeenRecording};
+use tcgclient::save;
+use tcgclient::spectate::{self, Spectator, SpectatorView, Spectators};
+use tcgclient::validation::{self, FormatRules};
+use tcgclient::texture_cache::{self, TextureCache};
+use tcgclient::view::PlayerView;
 
-use sdl2::{event::Event, pixels::Color};
+use rand::{thread_rng, Rng};
+use serde::Serialize;
+use sdl2::keyboard::TextInputUtil;
+use sdl2::render::{Canvas, TextureCreator};
+use sdl2::video::{Window, WindowContext};
+use sdl2::{event::Event, pixels::Color, EventPump, VideoSubsystem};
+use std::env;
+use std::fs;
+use std::process;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::path::{Path, PathBuf};
 const WINDOW_NAME: &str = "pokemon!!! :3";
 const BGCOLOR: Color = Color::RGB(255, 255, 255);
+const PREWARM_POLL_MS: u32 = 100;
+const LOG_SCROLL_LINES: usize = 10;
+// Network games wake up more often, to keep up with the other player
+const NET_POLL_MS: u32 = 20;
+
+fn exit_with_report(errors: &[DeckLoadError]) -> !
+{
+    eprintln!("{}", load_cards::error_report(errors));
+    process::exit(1)
+}
+
+/// Reports the problems with both decks together rather than stopping at the first.
+fn both_or_exit<T>(
+    deck1: Result<T, Vec<DeckLoadError>>,
+    deck2: Result<T, Vec<DeckLoadError>>,
+) -> (T, T)
+{
+    match (deck1, deck2) {
+        (Ok(deck1), Ok(deck2)) => (deck1, deck2),
+        (deck1, deck2) => {
+            let errors: Vec<_> =
+                [deck1.err(), deck2.err()].into_iter().flatten().flatten().collect();
+            exit_with_report(&errors)
+        },
+    }
+}
+
+fn timestamp() -> u64
+{
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+/// Writes the game so far to `logs/game-<timestamp>.txt` in the card root.
+fn export_log(
+    library: &CardLibrary,
+    card_loader: &load_cards::CardIndexer,
+    card_db: &CardDb,
+    log: &GameLog,
+    viewer: state::Player,
+) -> Result<PathBuf, String>
+{
+    let log_dir = library.root.join("logs");
+    fs::create_dir_all(&log_dir).map_err(|e| e.to_string())?;
+    let path = log_dir.join(format!("game-{}.txt", timestamp()));
+    fs::write(&path, log.to_text(viewer, card_loader, card_db)).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+/// Writes a recording when dropped, so it's kept however the game ends.
+struct RecordOnExit<T: Serialize>
+{
+    recording: T,
+    path: PathBuf,
+}
+
+impl<T: Serialize> Drop for RecordOnExit<T>
+{
+    fn drop(&mut self)
+    {
+        match replay::write(&self.recording, &self.path) {
+            Ok(()) => println!("Recorded this game to {}", self.path.display()),
+            Err(e) => eprintln!("Couldn't record this game: {}", e),
+        }
+    }
+}
+
+/// `--record` if there is one, otherwise a new file in `replays/` starting
+/// with `name`.
+fn record_path(library: &CardLibrary, options: &PlayOptions, name: &str) -> PathBuf
+{
+    match &options.record {
+        Some(path) => path.clone(),
+        None => library.replays_dir().join(format!("{}-{}.json", name, timestamp())),
+    }
+}
+
+/// `--save-file` if there is one, otherwise a new file in `saves/`.
+fn save_path(library: &CardLibrary, options: &PlayOptions) -> PathBuf
+{
+    match &options.save_file {
+        Some(path) => path.clone(),
+        None => library.saves_dir().join(format!("save-{}.json", timestamp())),
+    }
+}
+
+/// `--save-file` if there is one, otherwise the newest save in `saves/`.
+fn load_path(library: &CardLibrary, options: &PlayOptions) -> Option<PathBuf>
+{
+    if let Some(path) = &options.save_file {
+        return Some(path.clone());
+    }
+    let entries = fs::read_dir(library.saves_dir()).ok()?;
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().map(|ext| ext == "json").unwrap_or(false))
+        .max_by_key(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+}
+
+/// Replaces the game with a save, reading metadata for any cards that are new.
+fn load_save(
+    path: &Path,
+    library: &CardLibrary,
+    card_loader: &mut load_cards::CardIndexer,
+    card_db: &mut CardDb,
+) -> Result<state::State, String>
+{
+    let known = card_loader.len();
+    let st = save::load_game(path, card_loader)?;
+    let new_cards: Vec<state::Card> = (known..card_loader.len()).collect();
+    load_metadata(library, card_loader, card_db, &new_cards);
+    Ok(st)
+}
+
+fn load_metadata(
+    library: &CardLibrary,
+    card_loader: &load_cards::CardIndexer,
+    card_db: &mut CardDb,
+    cards: &[state::Card],
+)
+{
+    for card in cards {
+        if let Err(e) = card_db.load_card(library, card_loader, *card) {
+            eprintln!("Bad card metadata: {}", e);
+        }
+    }
+}
+
+/// The input for a key press or typed text, which depends on whether the
+/// player is typing a chat message.
+fn event_to_input(event: &Event, chatting: bool) -> Option<input::Input>
+{
+    match event {
+        Event::KeyDown { keycode: Some(k), .. } if chatting => input::keycode_to_chat_input(*k),
+        Event::KeyDown { keycode: Some(k), .. } => input::keycode_to_input(*k),
+        Event::TextInput { text, .. } if chatting => Some(input::Input::Type(text.clone())),
+        _ => None,
+    }
+}
+
+/// Typed text only comes through while chatting, so the key that starts a
+/// message doesn't end up in it.
+fn sync_text_input(text_input: &TextInputUtil, chatting: bool)
+{
+    if chatting && !text_input.is_active() {
+        text_input.start();
+    } else if !chatting && text_input.is_active() {
+        text_input.stop();
+    }
+}
+
+/// What's said when a key that only works in local games is pressed in a
+/// network one.
+fn not_in_network_games(inp: &input::Input) -> String
+{
+    use input::Input as I;
+    let action = match inp {
+        I::SwitchSides => "Switching sides",
+        I::Undo => "Undo",
+        I::Redo => "Redo",
+        I::SaveGame => "Saving",
+        I::LoadGame => "Loading a save",
+        I::ExportDeck => "Exporting decks",
+        _ => "That key",
+    };
+    format!("{} isn't available in network games", action)
+}
+
+/// `host` is the hosting player's side in a network game.
+fn listen_for_spectators(
+    options: &PlayOptions,
+    host: Option<state::Player>,
+) -> Result<Option<Spectators>, String>
+{
+    let Some(port) = options.spectate_port else {
+        return Ok(None);
+    };
+    let min_delay = match (options.spectate_delay, host) {
+        (Some(secs), _) => Duration::from_secs(secs),
+        (None, Some(_)) => spectate::DEFAULT_HOST_SPECTATE_DELAY,
+        (None, None) => Duration::ZERO,
+    };
+    let spectators = Spectators::new(port, min_delay, host)?;
+    println!("Spectators can watch on port {}", spectators.port());
+    Ok(Some(spectators))
+}
+
+/// Writes everything the viewing player owns to `decks/exported/`, in the native
+/// format and as a `-export.txt` for pasting into other clients.
+fn export_deck(
+    library: &CardLibrary,
+    sets: &SetTable,
+    card_loader: &load_cards::CardIndexer,
+    card_db: &CardDb,
+    st: &state::State,
+) -> Result<PathBuf, String>
+{
+    let cards = deck_export::layout_cards(st.current_layout());
+    let export_dir = library.decks_dir().join("exported");
+    fs::create_dir_all(&export_dir).map_err(|e| e.to_string())?;
+
+    let timestamp = timestamp();
+    let native_path = export_dir.join(format!("deck-{}.txt", timestamp));
+    let text_path = export_dir.join(format!("deck-{}-export.txt", timestamp));
+    fs::write(&native_path, deck_export::export_native(&cards, card_loader, card_db))
+        .map_err(|e| e.to_string())?;
+    fs::write(&text_path, deck_export::export_text(&cards, card_loader, sets, card_db))
+        .map_err(|e| e.to_string())?;
+    Ok(native_path)
+}
+
+/// `--format` if given, otherwise no format in particular.
+fn format_rules(library: &CardLibrary, format: Option<&str>) -> Result<FormatRules, String>
+{
+    match format {
+        Some(format) => FormatRules::load(library, format).map_err(|e| {
+            let available = FormatRules::available(library);
+            format!("{}\nFormats available: {}", e, available.join(", "))
+        }),
+        None => Ok(FormatRules::default()),
+    }
+}
+
+fn list_decks(library: &CardLibrary)
+{
+    let decks = library.deck_files();
+    if decks.is_empty() {
+        println!("No decks in {}", library.decks_dir().display());
+        return;
+    }
+    let mut current_dir = None;
+    for deck in decks {
+        let dir = deck.parent().map(Path::to_path_buf);
+        if dir != current_dir {
+            match &dir {
+                Some(dir) if dir != Path::new("") => println!("{}/", dir.display()),
+                _ => (),
+            }
+            current_dir = dir;
+        }
+        println!("  {}", deck.display());
+    }
+}
+
+/// Exits with 1 if the deck can't be loaded or isn't legal.
+fn validate_deck(library: &CardLibrary, deck: &str, format: Option<&str>) -> Result<(), String>
+{
+    let rules = format_rules(library, format)?;
+    let sets = SetTable::load(library)?;
+    let deck_path = library.find_deck(deck);
+    let deck_list = match load_cards::load_deck_list(library, &sets, &deck_path) {
+        Ok(deck_list) => deck_list,
+        Err(errors) => exit_with_report(&errors),
+    };
+    let card_loader = load_cards::CardIndexer::make(&[&deck_list]);
+    let (card_db, _) = CardDb::load(library, &card_loader);
+    let report = validation::validate(&deck_list, &rules, &card_loader, &card_db);
+
+    for violation in report.violations.iter() {
+        println!("{}", violation);
+    }
+    if !report.unchecked.is_empty() {
+        println!("No metadata to check: {}", report.unchecked.join(", "));
+    }
+    if report.is_legal() {
+        println!("{} is {} legal", deck_path.display(), rules.name);
+        Ok(())
+    } else {
+        println!("{} isn't {} legal", deck_path.display(), rules.name);
+        process::exit(1)
+    }
+}
+
+/// Shows the deck picker for whichever players didn't pass a deck on the
+/// command line. None if the window was closed before everyone picked.
+fn pick_decks(
+    canvas: &mut Canvas<Window>,
+    event_pump: &mut EventPump,
+    tex_creator: &TextureCreator<WindowContext>,
+    library: &CardLibrary,
+    sets: &SetTable,
+    rules: &FormatRules,
+    options: &PlayOptions,
+) -> Result<Option<(PathBuf, PathBuf)>, String>
+{
+    let mut deck1 = options.deck1.as_deref().map(|deck| library.find_deck(deck));
+    let mut deck2 = options.deck2.as_deref().map(|deck| library.find_deck(deck));
+    let pickers: Vec<state::Player> =
+        [(state::Player::Player1, &deck1), (state::Player::Player2, &deck2)]
+            .into_iter()
+            .filter(|(_, deck)| deck.is_none())
+            .map(|(player, _)| player)
+            .collect();
+    if pickers.is_empty() {
+        return Ok(deck1.zip(deck2));
+    }
+
+    let mut picker = DeckPicker::make(library, sets, rules, pickers);
+    let mut textures =
+        TextureCache::new(tex_creator, library, texture_cache::DEFAULT_TEXTURE_BUDGET_BYTES);
+    while !picker.is_done() {
+        let new_cards = picker.load_highlighted();
+        if !new_cards.is_empty() {
+            textures.prewarm(&new_cards, &picker.indexer);
+        }
+
+        canvas.set_draw_color(BGCOLOR);
+        canvas.clear();
+        draw_board::draw_deck_picker(canvas, &picker, &mut textures)?;
+        canvas.present();
+
+        let event = event_pump.wait_event_timeout(PREWARM_POLL_MS);
+        textures.pump();
+        match event {
+            Some(Event::Quit { .. }) => return Ok(None),
+            Some(Event::KeyDown { keycode: Some(k), .. }) => {
+                if let Some(inp) = input::keycode_to_input(k) {
+                    picker.update(&inp);
+                }
+            },
+            _ => (),
+        }
+    }
+
+    for (player, path) in picker.chosen() {
+        match player {
+            state::Player::Player1 => deck1 = Some(path),
+            state::Player::Player2 => deck2 = Some(path),
+        }
+    }
+    Ok(deck1.zip(deck2))
+}
+
+/// Picks decks (unless they were all given on the command line) and sets up
+/// a fresh board. None if the window was closed while picking.
+fn new_game(
+    canvas: &mut Canvas<Window>,
+    event_pump: &mut EventPump,
+    tex_creator: &TextureCreator<WindowContext>,
+    library: &CardLibrary,
+    sets: &SetTable,
+    rules: &FormatRules,
+    options: &PlayOptions,
+) -> Result<Option<(state::State, load_cards::CardIndexer, CardDb)>, String>
+{
+    let picked = pick_decks(canvas, event_pump, tex_creator, library, sets, rules, options)?;
+    let Some((deck1_path, deck2_path)) = picked else {
+        return Ok(None);
+    };
+    let (deck1_list, deck2_list) = both_or_exit(
+        load_cards::load_deck_list(library, sets, &deck1_path),
+        load_cards::load_deck_list(library, sets, &deck2_path),
+    );
+    let card_loader = load_cards::CardIndexer::make(&[&deck1_list, &deck2_list]);
+    let (card_db, card_db_errors) = CardDb::load(library, &card_loader);
+    for error in card_db_errors {
+        eprintln!("Bad card metadata: {}", error);
+    }
+    for (deck_path, deck_list) in [(&deck1_path, &deck1_list), (&deck2_path, &deck2_list)] {
+        let report = validation::validate(deck_list, rules, &card_loader, &card_db);
+        for violation in report.violations.iter() {
+            eprintln!("{} isn't {} legal: {}", deck_path.display(), rules.name, violation);
+        }
+    }
+    let (deck1, deck2) = both_or_exit(
+        card_loader.build_deck(&deck1_path, &deck1_list),
+        card_loader.build_deck(&deck2_path, &deck2_list),
+    );
+
+    let seed = options.seed.unwrap_or_else(|| thread_rng().gen());
+    println!("Seed: {} (pass --seed {} to play this game again)", seed, seed);
+    let mut st = state::State::make(deck1, deck2, seed);
+    st.setup();
+    st.start_on(options.first_player);
+    Ok(Some((st, card_loader, card_db)))
+}
+
+/// Loads `--load` if it was given, otherwise starts a new game.
+fn start_game(
+    canvas: &mut Canvas<Window>,
+    event_pump: &mut EventPump,
+    tex_creator: &TextureCreator<WindowContext>,
+    library: &CardLibrary,
+    sets: &SetTable,
+    rules: &FormatRules,
+    options: &PlayOptions,
+) -> Result<Option<(state::State, load_cards::CardIndexer, CardDb)>, String>
+{
+    let game = match &options.load {
+        Some(save_path) => {
+            let mut card_loader = load_cards::CardIndexer::default();
+            let mut card_db = CardDb::default();
+            let st = load_save(save_path, library, &mut card_loader, &mut card_db)?;
+            println!("Loaded {} (seed {})", save_path.display(), st.seed());
+            Some((st, card_loader, card_db))
+        },
+        None => new_game(canvas, event_pump, tex_creator, library, sets, rules, options)?,
+    };
+    if let Some((_, card_loader, _)) = &game {
+        for missing in card_loader.missing_images(library) {
+            eprintln!("Using a placeholder for {}", missing);
+        }
+    }
+    Ok(game)
+}
 
 fn main() -> Result<(), String>
 {
-    let sdl_context = sdl2::init()?;
+    let cli = match Cli::parse(env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            process::exit(2)
+        },
+    };
+    if let Command::Help = cli.command {
+        println!("{}", cli::USAGE);
+        return Ok(());
+    }
 
-    // Video init
-    let video_subsys = sdl_context.video()?;
-    let _image_context =
-        sdl2::image::init(sdl2::image::InitFlag::PNG | sdl2::image::InitFlag::JPG)?;
+    let library = CardLibrary::resolve(cli.card_root.as_deref())?;
+    match cli.command {
+        Command::Play(options) => {
+            println!("{}", library);
+            play(&library, &options)
+        },
+        Command::Validate { deck, format } => validate_deck(&library, &deck, format.as_deref()),
+        Command::ListDecks => {
+            list_decks(&library);
+            Ok(())
+        },
+        Command::Replay { file, options } => {
+            println!("{}", library);
+            watch_replay(&library, &file, &options)
+        },
+        Command::Host(options) => {
+            println!("{}", library);
+            host_game(&library, &options)
+        },
+        Command::Join { address, options } => {
+            println!("{}", library);
+            join_game(&library, &address, &options)
+        },
+        Command::Watch { view, delay, options } => {
+            println!("{}", library);
+            watch_game(&library, view, delay, &options)
+        },
+        Command::Help => unreachable!(),
+    }
+}
+
+fn make_canvas(
+    video_subsys: &VideoSubsystem,
+    options: &PlayOptions,
+) -> Result<Canvas<Window>, String>
+{
     let window = video_subsys
-        .window(WINDOW_NAME, WINDOW_WIDTH, WINDOW_HEIGHT)
+        .window(WINDOW_NAME, options.window_width, options.window_height)
         .position_centered()
+        .resizable()
         .build()
         .map_err(|e| e.to_string())?;
     let mut canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
+    // The board is laid out for WINDOW_WIDTH x WINDOW_HEIGHT; SDL scales it to the window
+    canvas.set_logical_size(WINDOW_WIDTH, WINDOW_HEIGHT).map_err(|e| e.to_string())?;
+    Ok(canvas)
+}
+
+fn watch_replay(library: &CardLibrary, file: &Path, options: &PlayOptions) -> Result<(), String>
+{
+    let mut card_loader = load_cards::CardIndexer::default();
+    let mut replay = match replay::read(file)? {
+        Recorded::Game(recording) => Replay::make(&recording, &mut card_loader)?,
+        Recorded::Seen(recording) => Replay::seen(&recording, &mut card_loader)?,
+    };
+    for warning in replay.warnings.iter() {
+        eprintln!("Replay doesn't match the recording: {}", warning);
+    }
+    let (card_db, card_db_errors) = CardDb::load(library, &card_loader);
+    for error in card_db_errors {
+        eprintln!("Bad card metadata: {}", error);
+    }
+
+    let sdl_context = sdl2::init()?;
+    let video_subsys = sdl_context.video()?;
+    let _image_context =
+        sdl2::image::init(sdl2::image::InitFlag::PNG | sdl2::image::InitFlag::JPG)?;
+    let mut canvas = make_canvas(&video_subsys, options)?;
     let tex_creator = canvas.texture_creator();
+    let mut event_pump = sdl_context.event_pump()?;
 
-    let deck1_filename = "2009/amu_long.txt";
-    let deck2_filename = "2009/kingdra_long.txt";
-    let (card_loader, card_textures) =
-        load_cards::CardIndexer::make(deck1_filename, deck2_filename, &tex_creator);
-    let deck1 = card_loader.build_deck(deck1_filename);
-    let deck2 = card_loader.build_deck(deck2_filename);
+    let mut textures =
+        TextureCache::new(&tex_creator, library, texture_cache::DEFAULT_TEXTURE_BUDGET_BYTES);
+    let starting_cards: Vec<state::Card> = (0..card_loader.len()).collect();
+    textures.prewarm(&starting_cards, &card_loader);
+    let mut log_view = LogView { visible: true, scroll: 0 };
+    // Digits typed so far of a turn to jump to
+    let mut typed_turn: Option<usize> = None;
 
-    let mut st = state::State::make(deck1, deck2);
-    st.setup();
+    loop {
+        canvas.set_draw_color(BGCOLOR);
+        canvas.clear();
+        let mut art = CardArt { textures: &mut textures, indexer: &card_loader, db: &card_db };
+        let view = replay.view();
+        draw_board::draw(&mut canvas, view, &mut art)?;
+        if log_view.visible {
+            draw_board::draw_log(&mut canvas, &replay.log, view.viewer, &log_view, &art)?;
+        }
+        draw_board::draw_chat(&mut canvas, &replay.log, view)?;
+        let mut status = format!(
+            "Step {}/{}, turn {}/{}",
+            replay.position(),
+            replay.steps(),
+            replay.turn(),
+            replay.turns()
+        );
+        if let Some(turn) = typed_turn {
+            status.push_str(&format!(", go to turn {}", turn));
+        }
+        draw_board::draw_status(&mut canvas, &status)?;
+        canvas.present();
+
+        let event = event_pump.wait_event_timeout(PREWARM_POLL_MS);
+        textures.pump();
+        use input::ReplayInput as R;
+        match event {
+            Some(Event::Quit { .. }) => return Ok(()),
+            Some(Event::KeyDown { keycode: Some(k), .. }) => {
+                match input::keycode_to_replay_input(k) {
+                    Some(R::StepBack) => replay.step_back(),
+                    Some(R::StepForward) => replay.step_forward(),
+                    Some(R::PreviousTurn) => replay.previous_turn(),
+                    Some(R::NextTurn) => replay.next_turn(),
+                    Some(R::Start) => replay.seek(0),
+                    Some(R::End) => replay.seek(replay.steps()),
+                    Some(R::Digit(digit)) => {
+                        typed_turn = Some(typed_turn.unwrap_or(0).saturating_mul(10) + digit)
+                    },
+                    Some(R::JumpToTurn) => {
+                        if let Some(turn) = typed_turn.take() {
+                            replay.jump_to_turn(turn);
+                        }
+                    },
+                    Some(R::ClearTyped) => typed_turn = None,
+                    Some(R::ToggleLog) => log_view.visible = !log_view.visible,
+                    None => (),
+                }
+            },
+            _ => (),
+        }
+    }
+}
+
+fn play(library: &CardLibrary, options: &PlayOptions) -> Result<(), String>
+{
+    let rules = format_rules(library, options.format.as_deref())?;
+    let sdl_context = sdl2::init()?;
+
+    // Video init
+    let video_subsys = sdl_context.video()?;
+    let _image_context =
+        sdl2::image::init(sdl2::image::InitFlag::PNG | sdl2::image::InitFlag::JPG)?;
+    let mut canvas = make_canvas(&video_subsys, options)?;
+    let tex_creator = canvas.texture_creator();
 
     let mut event_pump = sdl_context.event_pump()?;
 
+    let sets = SetTable::load(library)?;
+    let game =
+        start_game(&mut canvas, &mut event_pump, &tex_creator, library, &sets, &rules, options)?;
+    let Some((mut st, mut card_loader, mut card_db)) = game else {
+        return Ok(());
+    };
+
+    let mut textures =
+        TextureCache::new(&tex_creator, library, texture_cache::DEFAULT_TEXTURE_BUDGET_BYTES);
+    let starting_cards: Vec<state::Card> = (0..card_loader.len()).collect();
+    textures.prewarm(&starting_cards, &card_loader);
+
+    let mut history = History::new(options.undo_depth);
+    let mut log = GameLog::default();
+    let mut log_view = LogView::default();
+    let mut recorder = RecordOnExit {
+        recording: Recording::start(&st, &card_loader, options.undo_depth),
+        path: record_path(library, options, "game"),
+    };
+    let mut spectators = listen_for_spectators(options, None)?;
+    let poll_ms = if spectators.is_some() { NET_POLL_MS } else { PREWARM_POLL_MS };
+    // Why the last key did nothing, until the next one
+    let mut status: Option<String> = None;
+
+    let text_input = video_subsys.text_input();
+
     'running: loop {
-        match event_pump.wait_event() {
-            Event::Quit { .. } => {
-                break 'running;
-            },
-            Event::KeyDown { keycode: Some(k), .. } => {
-                if let Some(inp) = input::keycode_to_input(k) {
-                    st.update(&inp);
+        let chatting = matches!(st.input_mode, state::InputMode::Chat { .. });
+        sync_text_input(&text_input, chatting);
+        // Wake up now and then to upload pre-warmed textures
+        let event = event_pump.wait_event_timeout(poll_ms);
+        textures.pump();
+        if let Some(spectators) = &mut spectators {
+            spectators.poll(&st, &|player| st.input_mode_of(player), &card_loader);
+        }
+        match event {
+            None => continue,
+            Some(Event::Quit { .. }) => break 'running,
+            Some(event) => {
+                let inp = event_to_input(&event, chatting);
+                if inp.is_some() {
+                    status = None;
+                }
+                match inp {
+                    Some(input::Input::ExportDeck) => {
+                        match export_deck(library, &sets, &card_loader, &card_db, &st) {
+                            Ok(path) => println!("Exported deck to {}", path.display()),
+                            Err(e) => eprintln!("Couldn't export deck: {}", e),
+                        }
+                    },
+                    Some(input::Input::ExportLog) => {
+                        let viewer = st.currently_viewing;
+                        match export_log(library, &card_loader, &card_db, &log, viewer) {
+                            Ok(path) => println!("Exported log to {}", path.display()),
+                            Err(e) => eprintln!("Couldn't export log: {}", e),
+                        }
+                    },
+                    Some(input::Input::SaveGame) => {
+                        let path = save_path(library, options);
+                        match save::save_game(&path, &st, &card_loader) {
+                            Ok(()) => println!("Saved to {}", path.display()),
+                            Err(e) => eprintln!("Couldn't save: {}", e),
+                        }
+                    },
+                    Some(input::Input::LoadGame) => match load_path(library, options) {
+                        Some(path) => {
+                            match load_save(&path, library, &mut card_loader, &mut card_db) {
+                                Ok(loaded) => {
+                                    history.record(std::mem::replace(&mut st, loaded));
+                                    recorder.recording.record_load(&st, &card_loader);
+                                    println!("Loaded {} (Z to go back)", path.display());
+                                },
+                                Err(e) => eprintln!("Couldn't load: {}", e),
+                            }
+                        },
+                        None => eprintln!("No saves in {}", library.saves_dir().display()),
+                    },
+                    Some(input::Input::ToggleLog) => log_view.visible = !log_view.visible,
+                    Some(input::Input::ScrollLogUp) => log_view.scroll_up(LOG_SCROLL_LINES, &log),
+                    Some(input::Input::ScrollLogDown) => log_view.scroll_down(LOG_SCROLL_LINES),
+                    Some(inp) => {
+                        let logged = log.len();
+                        if replay::play_input(&mut st, &mut history, &mut log, &inp) {
+                            let events = &log.events()[logged..];
+                            recorder.recording.record_input(&inp, &st, events, &card_loader);
+                        } else {
+                            // Only undo and redo can have nothing to do
+                            let action = if inp == input::Input::Undo { "undo" } else { "redo" };
+                            status = Some(format!("Nothing to {}", action));
+                        }
+                    },
+                    None => (),
                 }
             },
-            _ => (),
         }
 
         // Draw (specific)
         canvas.set_draw_color(BGCOLOR);
         canvas.clear();
 
-        draw_board::draw(&mut canvas, &st, &card_textures)?;
+        let mut art = CardArt { textures: &mut textures, indexer: &card_loader, db: &card_db };
+        let view = PlayerView::of(&st);
+        draw_board::draw(&mut canvas, &view, &mut art)?;
+        if log_view.visible {
+            draw_board::draw_log(&mut canvas, &log, view.viewer, &log_view, &art)?;
+        }
+        draw_board::draw_chat(&mut canvas, &log, &view)?;
+        if let Some(status) = &status {
+            draw_board::draw_status(&mut canvas, status)?;
+        }
 
         /* let flareon_index = card_loader.index_of("flareon-ex-delta-species-ds-108");
         let (flareon_w, flareon_h) = card_loader.get_dimensions(flareon_index);
@@ -79,3 +735,304 @@ fn main() -> Result<(), String>
 
     Ok(())
 }
+
+fn host_game(library: &CardLibrary, options: &PlayOptions) -> Result<(), String>
+{
+    let rules = format_rules(library, options.format.as_deref())?;
+    let sdl_context = sdl2::init()?;
+    let video_subsys = sdl_context.video()?;
+    let _image_context =
+        sdl2::image::init(sdl2::image::InitFlag::PNG | sdl2::image::InitFlag::JPG)?;
+    let mut canvas = make_canvas(&video_subsys, options)?;
+    let tex_creator = canvas.texture_creator();
+    let mut event_pump = sdl_context.event_pump()?;
+
+    let sets = SetTable::load(library)?;
+    let game =
+        start_game(&mut canvas, &mut event_pump, &tex_creator, library, &sets, &rules, options)?;
+    let Some((st, card_loader, card_db)) = game else {
+        return Ok(());
+    };
+
+    let mut textures =
+        TextureCache::new(&tex_creator, library, texture_cache::DEFAULT_TEXTURE_BUDGET_BYTES);
+    let starting_cards: Vec<state::Card> = (0..card_loader.len()).collect();
+    textures.prewarm(&starting_cards, &card_loader);
+
+    let mut host = Host::new(st, options.port)?;
+    println!(
+        "Hosting game {} on port {}, waiting for {} to join",
+        host.game_id(),
+        host.port(),
+        host.player().other()
+    );
+    let mut spectators = listen_for_spectators(options, Some(host.player()))?;
+    let mut recorder = RecordOnExit {
+        recording: Recording::start(host.state(), &card_loader, options.undo_depth),
+        path: record_path(library, options, "game"),
+    };
+    let mut log = GameLog::default();
+    let mut log_view = LogView::default();
+    // Why the last key did nothing, until the next one
+    let mut status: Option<String> = None;
+    let text_input = video_subsys.text_input();
+
+    loop {
+        let chatting = matches!(host.state().input_mode, state::InputMode::Chat { .. });
+        sync_text_input(&text_input, chatting);
+        let event = event_pump.wait_event_timeout(NET_POLL_MS);
+        textures.pump();
+        log.extend(host.poll(&card_loader));
+        recorder.recording.steps.extend(host.take_recorded());
+        if let Some(spectators) = &mut spectators {
+            spectators.poll(host.state(), &|player| host.input_mode_of(player), &card_loader);
+        }
+        if let Some(Event::KeyDown { .. }) = event {
+            status = None;
+        }
+        match event {
+            Some(Event::Quit { .. }) => return Ok(()),
+            Some(event) => match event_to_input(&event, chatting) {
+                Some(input::Input::ExportDeck) => {
+                    match export_deck(library, &sets, &card_loader, &card_db, host.state()) {
+                        Ok(path) => println!("Exported deck to {}", path.display()),
+                        Err(e) => eprintln!("Couldn't export deck: {}", e),
+                    }
+                },
+                Some(input::Input::ExportLog) => {
+                    match export_log(library, &card_loader, &card_db, &log, host.player()) {
+                        Ok(path) => println!("Exported log to {}", path.display()),
+                        Err(e) => eprintln!("Couldn't export log: {}", e),
+                    }
+                },
+                Some(input::Input::SaveGame) => {
+                    let path = save_path(library, options);
+                    match save::save_game(&path, host.state(), &card_loader) {
+                        Ok(()) => println!("Saved to {}", path.display()),
+                        Err(e) => eprintln!("Couldn't save: {}", e),
+                    }
+                },
+                Some(input::Input::ToggleLog) => log_view.visible = !log_view.visible,
+                Some(input::Input::ScrollLogUp) => log_view.scroll_up(LOG_SCROLL_LINES, &log),
+                Some(input::Input::ScrollLogDown) => log_view.scroll_down(LOG_SCROLL_LINES),
+                Some(inp) if net::is_game_input(&inp) && host.is_waiting() => {
+                    status = Some(format!("Paused until {} is back", host.player().other()))
+                },
+                Some(inp) if net::is_game_input(&inp) => {
+                    log.extend(host.play(&inp, &card_loader));
+                    recorder.recording.steps.extend(host.take_recorded());
+                },
+                Some(inp) => status = Some(not_in_network_games(&inp)),
+                None => (),
+            },
+            None => (),
+        }
+
+        canvas.set_draw_color(BGCOLOR);
+        canvas.clear();
+        let mut art = CardArt { textures: &mut textures, indexer: &card_loader, db: &card_db };
+        let view = PlayerView::of(host.state());
+        draw_board::draw(&mut canvas, &view, &mut art)?;
+        if log_view.visible {
+            draw_board::draw_log(&mut canvas, &log, view.viewer, &log_view, &art)?;
+        }
+        draw_board::draw_chat(&mut canvas, &log, &view)?;
+        if host.is_waiting() {
+            let waiting =
+                format!("Waiting for opponent: {} dropped out", host.player().other());
+            draw_board::draw_waiting(&mut canvas, &waiting)?;
+        }
+        if let Some(status) = &status {
+            draw_board::draw_status(&mut canvas, status)?;
+        } else if !host.is_connected() && !host.is_waiting() {
+            let waiting = format!(
+                "Waiting for {} to join on port {}",
+                host.player().other(),
+                host.port()
+            );
+            draw_board::draw_status(&mut canvas, &waiting)?;
+        }
+        canvas.present();
+    }
+}
+
+fn join_game(library: &CardLibrary, address: &str, options: &PlayOptions) -> Result<(), String>
+{
+    let address = net::with_port(address, options.port);
+    let mut guest = Guest::join(&address, options.game_id.clone())?;
+    println!(
+        "Joined game {} at {} as {} (to rejoin after a restart, add --game {})",
+        guest.game_id(),
+        address,
+        guest.player(),
+        guest.game_id()
+    );
+
+    let sdl_context = sdl2::init()?;
+    let video_subsys = sdl_context.video()?;
+    let _image_context =
+        sdl2::image::init(sdl2::image::InitFlag::PNG | sdl2::image::InitFlag::JPG)?;
+    let mut canvas = make_canvas(&video_subsys, options)?;
+    let tex_creator = canvas.texture_creator();
+    let mut event_pump = sdl_context.event_pump()?;
+
+    // Filled in from the host, so cards get the same indices as theirs
+    let mut card_loader = load_cards::CardIndexer::default();
+    let mut card_db = CardDb::default();
+    let mut textures =
+        TextureCache::new(&tex_creator, library, texture_cache::DEFAULT_TEXTURE_BUDGET_BYTES);
+    let mut log = GameLog::default();
+    let mut log_view = LogView::default();
+    let mut recorder = RecordOnExit {
+        recording: SeenRecording::start(guest.player()),
+        path: record_path(library, options, "seen"),
+    };
+    // Why the host turned us away, once it has
+    let mut disconnected: Option<String> = None;
+    // Why the last key did nothing, until the next one
+    let mut status: Option<String> = None;
+    let text_input = video_subsys.text_input();
+    // Kept here rather than read from the view, which only catches up a
+    // round trip after the key that starts or ends a message
+    let mut chatting = false;
+
+    loop {
+        sync_text_input(&text_input, chatting);
+        let event = event_pump.wait_event_timeout(NET_POLL_MS);
+        textures.pump();
+        if disconnected.is_none() {
+            let views_received = guest.views_received();
+            match guest.poll(&mut card_loader) {
+                Ok((events, new_cards)) => {
+                    load_metadata(library, &card_loader, &mut card_db, &new_cards);
+                    textures.prewarm(&new_cards, &card_loader);
+                    if guest.views_received() != views_received || !events.is_empty() {
+                        recorder.recording.record(guest.view(), &events, &card_loader);
+                    }
+                    log.extend(events);
+                },
+                Err(e) => {
+                    eprintln!("Left the game: {}", e);
+                    disconnected = Some(e);
+                },
+            }
+        }
+        if let Some(Event::KeyDown { .. }) = event {
+            status = None;
+        }
+        match event {
+            Some(Event::Quit { .. }) => return Ok(()),
+            Some(event) => match event_to_input(&event, chatting) {
+                Some(input::Input::ExportLog) => {
+                    match export_log(library, &card_loader, &card_db, &log, guest.player()) {
+                        Ok(path) => println!("Exported log to {}", path.display()),
+                        Err(e) => eprintln!("Couldn't export log: {}", e),
+                    }
+                },
+                Some(input::Input::ToggleLog) => log_view.visible = !log_view.visible,
+                Some(input::Input::ScrollLogUp) => log_view.scroll_up(LOG_SCROLL_LINES, &log),
+                Some(input::Input::ScrollLogDown) => log_view.scroll_down(LOG_SCROLL_LINES),
+                Some(_) if disconnected.is_some() => (),
+                Some(inp) if net::is_game_input(&inp) && guest.outage().is_some() => {
+                    status = Some("Paused until the host is back".to_string())
+                },
+                Some(inp) if net::is_game_input(&inp) => {
+                    chatting = match inp {
+                        input::Input::Chat => true,
+                        input::Input::Select | input::Input::Cancel => false,
+                        _ => chatting,
+                    };
+                    guest.send(&inp)
+                },
+                Some(inp) => status = Some(not_in_network_games(&inp)),
+                None => (),
+            },
+            None => (),
+        }
+
+        canvas.set_draw_color(BGCOLOR);
+        canvas.clear();
+        let mut art = CardArt { textures: &mut textures, indexer: &card_loader, db: &card_db };
+        draw_board::draw(&mut canvas, guest.view(), &mut art)?;
+        if log_view.visible {
+            draw_board::draw_log(&mut canvas, &log, guest.player(), &log_view, &art)?;
+        }
+        draw_board::draw_chat(&mut canvas, &log, guest.view())?;
+        if let Some(reason) = &disconnected {
+            draw_board::draw_status(&mut canvas, &format!("Disconnected: {}", reason))?;
+        } else {
+            if let Some(reason) = guest.outage() {
+                let waiting = format!("Waiting for opponent: reconnecting ({})", reason);
+                draw_board::draw_waiting(&mut canvas, &waiting)?;
+            }
+            if let Some(status) = &status {
+                draw_board::draw_status(&mut canvas, status)?;
+            }
+        }
+        canvas.present();
+    }
+}
+
+fn watch_game(
+    library: &CardLibrary,
+    view: SpectatorView,
+    delay: Duration,
+    options: &PlayOptions,
+) -> Result<(), String>
+{
+    let port = options.spectate_port.unwrap_or(spectate::DEFAULT_SPECTATE_PORT);
+    let address = format!("127.0.0.1:{}", port);
+    let mut spectator = Spectator::watch(&address, view, delay)?;
+    println!("Watching {} ({})", address, view);
+
+    let sdl_context = sdl2::init()?;
+    let video_subsys = sdl_context.video()?;
+    let _image_context =
+        sdl2::image::init(sdl2::image::InitFlag::PNG | sdl2::image::InitFlag::JPG)?;
+    let mut canvas = make_canvas(&video_subsys, options)?;
+    let tex_creator = canvas.texture_creator();
+    let mut event_pump = sdl_context.event_pump()?;
+
+    // Filled in from the game, as when joining one
+    let mut card_loader = load_cards::CardIndexer::default();
+    let mut card_db = CardDb::default();
+    let mut textures =
+        TextureCache::new(&tex_creator, library, texture_cache::DEFAULT_TEXTURE_BUDGET_BYTES);
+    let mut disconnected: Option<String> = None;
+
+    loop {
+        // Keys are ignored: there's nothing a spectator can change
+        let event = event_pump.wait_event_timeout(NET_POLL_MS);
+        textures.pump();
+        if let Some(Event::Quit { .. }) = event {
+            return Ok(());
+        }
+        if disconnected.is_none() {
+            match spectator.poll(&mut card_loader) {
+                Ok(new_cards) => {
+                    load_metadata(library, &card_loader, &mut card_db, &new_cards);
+                    textures.prewarm(&new_cards, &card_loader);
+                },
+                Err(e) => {
+                    eprintln!("Lost the game: {}", e);
+                    disconnected = Some(e);
+                },
+            }
+        }
+
+        canvas.set_draw_color(BGCOLOR);
+        canvas.clear();
+        let mut art = CardArt { textures: &mut textures, indexer: &card_loader, db: &card_db };
+        if let Some(view) = spectator.view() {
+            draw_board::draw(&mut canvas, view, &mut art)?;
+        }
+        let status = match (&disconnected, spectator.view()) {
+            (Some(reason), _) => format!("Disconnected: {}", reason),
+            (None, None) => format!("Watching ({}), waiting for the game", view),
+            (None, Some(_)) if delay.is_zero() => format!("Watching ({})", view),
+            (None, Some(_)) => format!("Watching ({}, {}s behind)", view, delay.as_secs()),
+        };
+        draw_board::draw_status(&mut canvas, &status)?;
+        canvas.present();
+    }
+}
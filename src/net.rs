@@ -0,0 +1,940 @@
+//! Network play over TCP. The host has the only real `State` and plays the
+//! side it started on; the other player joins, sends their inputs and draws
+//! the `PlayerView` the host sends back. Events are sent as they'd see them
+//! too (see `view::event_seen_by`), so what's hidden from them never leaves
+//! the host. Each message is one line of JSON:
+//!
+//! ```json
+//! {"Hello":{"version":4,"game_id":null,"cards_seen":0,"events_seen":0}}
+//! {"Input":"Deck"}
+//! "Ping"
+//! ```
+//!
+//! from the joining player, and from the host:
+//!
+//! ```json
+//! {"Welcome":{"version":4,"player":"Player2","game_id":"5f3a91c2"}}
+//! {"Cards":["DS/flareon-ex-delta-species-ds-108","MT/pachirisu-mt-86"]}
+//! {"View":{"viewer":"Player2","player1":{...},"player2":{...},"deck_shown":[],...}}
+//! {"Events":[{"DeckShuffled":{"player":"Player1"}}]}
+//! ```
+//!
+//! `Cards` lists the host's cards in `Card` index order, so the joining
+//! player's `CardIndexer` gives every card the same index and views and
+//! events can be sent as they are. Only cards new since the last `Cards` are
+//! sent.
+//!
+//! Each player has their own highlight and selection: the host keeps the
+//! joining player's `InputMode` and swaps it in to apply their inputs.
+//!
+//! A game outlasts a dropped connection. Both sides send a `Ping` when
+//! they've sent nothing else for a second, and count the connection as lost
+//! after a few seconds without hearing anything. The joining player then
+//! keeps reconnecting, saying hello with the game's ID and how many cards
+//! and events they already have, and the host sends the view and whatever
+//! they missed. Play pauses until they're back. Once someone has joined,
+//! only a hello with the game's ID gets in (`join --game` after a restart),
+//! and it takes over from any connection the host hasn't noticed is gone.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::game_log::GameEvent;
+use crate::input::Input;
+use crate::load_cards::CardIndexer;
+use crate::replay::{PlayedInput, RecordedStep};
+use crate::state::{InputMode, Player, State};
+use crate::view::{self, PlayerView};
+
+pub const NET_VERSION: u32 = 4;
+pub const DEFAULT_PORT: u16 = 7777;
+/// How long a new connection has to say hello.
+const HELLO_TIMEOUT: Duration = Duration::from_secs(5);
+/// How often to ping when there's nothing else to send.
+const PING_INTERVAL: Duration = Duration::from_secs(1);
+/// How long without hearing anything before a connection counts as lost.
+const SILENCE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Reconnecting is tried this often, on its own thread so the window stays
+/// responsive, giving up on each try after `RECONNECT_TIMEOUT`.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(2);
+const RECONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum ClientMessage
+{
+    /// `game_id` is None when joining for the first time.
+    Hello
+    {
+        version: u32,
+        game_id: Option<String>,
+        cards_seen: usize,
+        events_seen: usize,
+    },
+    Input(Input),
+    Ping,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum HostMessage
+{
+    Welcome
+    {
+        version: u32, player: Player, game_id: String
+    },
+    /// The connection is about to close, and why. `try_again` is set when
+    /// the game might let them in later.
+    Refused
+    {
+        reason: String, try_again: bool
+    },
+    /// As `"<set>/<card>"`, continuing from the cards already sent.
+    Cards(Vec<String>),
+    View(Box<PlayerView>),
+    Events(Vec<GameEvent>),
+    Ping,
+}
+
+/// Inputs that change the shared game. Undo, loading a save and switching
+/// sides would change it for both players at once, and the rest only matter
+/// to the player pressing them.
+pub fn is_game_input(input: &Input) -> bool
+{
+    use Input as I;
+    !matches!(
+        input,
+        I::SwitchSides
+            | I::Undo
+            | I::Redo
+            | I::SaveGame
+            | I::LoadGame
+            | I::ExportDeck
+            | I::ExportLog
+            | I::ToggleLog
+            | I::ScrollLogUp
+            | I::ScrollLogDown
+    )
+}
+
+/// `address` can leave out the port.
+pub fn with_port(address: &str, port: u16) -> String
+{
+    if address.contains(':') {
+        address.to_string()
+    } else {
+        format!("{}:{}", address, port)
+    }
+}
+
+/// The indexer's cards from `from` on, as sent in `Cards`.
+pub fn card_names(indexer: &CardIndexer, from: usize) -> Vec<String>
+{
+    (from..indexer.len())
+        .map(|card| {
+            let (set, card_id) = indexer.identity(card);
+            format!("{}/{}", set, card_id)
+        })
+        .collect()
+}
+
+/// Adds the cards from a `Cards` message to the indexer, returning their
+/// indices.
+pub fn intern_cards(indexer: &mut CardIndexer, names: &[String]) -> Result<Vec<usize>, String>
+{
+    names
+        .iter()
+        .map(|name| match name.split_once('/') {
+            Some((set, card)) => Ok(indexer.intern(set, card)),
+            None => Err(format!("Got a bad card name {:?}", name)),
+        })
+        .collect()
+}
+
+/// One end of a TCP connection. Incoming lines are read on their own thread,
+/// so checking for messages never blocks.
+pub struct Connection<In>
+{
+    stream: TcpStream,
+    incoming: Receiver<Result<In, String>>,
+    // Held back until the messages before it have been handled
+    error: Option<String>,
+    last_heard: Instant,
+    last_sent: Instant,
+}
+
+impl<In: DeserializeOwned + Send + 'static> Connection<In>
+{
+    pub fn new(stream: TcpStream) -> Result<Self, String>
+    {
+        stream.set_nonblocking(false).map_err(|e| e.to_string())?;
+        stream.set_nodelay(true).map_err(|e| e.to_string())?;
+        let reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+        let (sender, incoming) = mpsc::channel();
+        thread::spawn(move || {
+            for line in reader.lines() {
+                let message = match line {
+                    Ok(line) => serde_json::from_str(&line)
+                        .map_err(|e| format!("Couldn't understand {:?}: {}", line, e)),
+                    Err(e) => Err(format!("Connection lost: {}", e)),
+                };
+                let failed = message.is_err();
+                if sender.send(message).is_err() || failed {
+                    return;
+                }
+            }
+            let _ = sender.send(Err("The other side closed the connection".to_string()));
+        });
+        let now = Instant::now();
+        Ok(Connection { stream, incoming, error: None, last_heard: now, last_sent: now })
+    }
+
+    pub fn send<Out: Serialize>(&mut self, message: &Out) -> Result<(), String>
+    {
+        let mut line = serde_json::to_string(message).map_err(|e| e.to_string())?;
+        line.push('\n');
+        self.stream.write_all(line.as_bytes()).map_err(|e| format!("Connection lost: {}", e))?;
+        self.last_sent = Instant::now();
+        Ok(())
+    }
+
+    /// Sends `ping` if nothing else has been sent for a while, so the other
+    /// side can tell the connection is still there.
+    pub fn keep_alive<Out: Serialize>(&mut self, ping: &Out) -> Result<(), String>
+    {
+        if self.last_sent.elapsed() >= PING_INTERVAL {
+            self.send(ping)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Nothing has arrived for long enough to give up on the other side.
+    pub fn is_silent(&self) -> bool
+    {
+        self.last_heard.elapsed() >= SILENCE_TIMEOUT
+    }
+
+    /// Messages that have arrived so far, without waiting. An error means
+    /// the connection is finished with.
+    pub fn receive(&mut self) -> Result<Vec<In>, String>
+    {
+        let mut messages = Vec::new();
+        while self.error.is_none() {
+            match self.incoming.try_recv() {
+                Ok(Ok(message)) => messages.push(message),
+                Ok(Err(e)) => self.error = Some(e),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => self.error = Some("Connection lost".to_string()),
+            }
+        }
+        if !messages.is_empty() {
+            self.last_heard = Instant::now();
+        }
+        match &self.error {
+            Some(e) if messages.is_empty() => Err(e.clone()),
+            _ => Ok(messages),
+        }
+    }
+
+    /// Waits for the next message.
+    pub fn receive_within(&mut self, timeout: Duration) -> Result<In, String>
+    {
+        match self.incoming.recv_timeout(timeout) {
+            Ok(message) => {
+                self.last_heard = Instant::now();
+                message
+            },
+            Err(RecvTimeoutError::Timeout) => Err("Timed out".to_string()),
+            Err(RecvTimeoutError::Disconnected) => Err("Connection lost".to_string()),
+        }
+    }
+}
+
+impl<In> Drop for Connection<In>
+{
+    fn drop(&mut self)
+    {
+        // The reader thread has its own handle, so the socket wouldn't close
+        // by itself
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+/// A connection that hasn't said hello yet.
+struct Pending
+{
+    connection: Connection<ClientMessage>,
+    address: SocketAddr,
+    since: Instant,
+}
+
+/// The hosting side of a network game.
+pub struct Host
+{
+    st: State,
+    player: Player,
+    guest_mode: InputMode,
+    listener: TcpListener,
+    pending: Vec<Pending>,
+    guest: Option<Connection<ClientMessage>>,
+    game_id: String,
+    /// Whether anyone has joined yet. After that only they can.
+    joined: bool,
+    /// Every event so far, to catch up a player who reconnects.
+    events: Vec<GameEvent>,
+    cards_sent: usize,
+    /// What's been played since `take_recorded`.
+    recorded: Vec<RecordedStep>,
+}
+
+impl Host
+{
+    /// Listens on `port`, playing the side `st` is viewing.
+    pub fn new(mut st: State, port: u16) -> Result<Self, String>
+    {
+        let listener = TcpListener::bind(("0.0.0.0", port))
+            .map_err(|e| format!("Couldn't listen on port {}: {}", port, e))?;
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+        let player = st.currently_viewing;
+        st.start_on(player.other());
+        let guest_mode = st.input_mode.clone();
+        st.start_on(player);
+        Ok(Host {
+            st,
+            player,
+            guest_mode,
+            listener,
+            pending: Vec::new(),
+            guest: None,
+            game_id: format!("{:08x}", rand::random::<u32>()),
+            joined: false,
+            events: Vec::new(),
+            cards_sent: 0,
+            recorded: Vec::new(),
+        })
+    }
+
+    /// The game as the host sees it.
+    pub fn state(&self) -> &State
+    {
+        &self.st
+    }
+
+    pub fn player(&self) -> Player
+    {
+        self.player
+    }
+
+    pub fn port(&self) -> u16
+    {
+        self.listener.local_addr().map(|address| address.port()).unwrap_or(0)
+    }
+
+    pub fn game_id(&self) -> &str
+    {
+        &self.game_id
+    }
+
+    pub fn is_connected(&self) -> bool
+    {
+        self.guest.is_some()
+    }
+
+    /// The other player joined and then dropped out, so the game is paused
+    /// until they're back.
+    pub fn is_waiting(&self) -> bool
+    {
+        self.joined && self.guest.is_none()
+    }
+
+    /// The host's mode for their side, the joining player's for theirs.
+    pub fn input_mode_of(&self, player: Player) -> InputMode
+    {
+        if player == self.player {
+            self.st.input_mode.clone()
+        } else {
+            self.guest_mode.clone()
+        }
+    }
+
+    /// Applies one of the host's own inputs, unless the game is paused.
+    pub fn play(&mut self, input: &Input, indexer: &CardIndexer) -> Vec<GameEvent>
+    {
+        if self.is_waiting() {
+            return Vec::new();
+        }
+        let events = self.st.update(input);
+        let played = PlayedInput::after(input, &self.st, &events, indexer);
+        self.recorded.push(RecordedStep::Input(played));
+        self.events.extend(events.iter().cloned());
+        self.send_update(&events, indexer);
+        events
+    }
+
+    /// Everything played by either player since the last call, for
+    /// recording the game.
+    pub fn take_recorded(&mut self) -> Vec<RecordedStep>
+    {
+        std::mem::take(&mut self.recorded)
+    }
+
+    /// Lets a player join or come back, and applies what they've sent.
+    /// Returns the events their inputs caused, for the host's log.
+    pub fn poll(&mut self, indexer: &CardIndexer) -> Vec<GameEvent>
+    {
+        self.accept(indexer);
+
+        let Some(guest) = &mut self.guest else {
+            return Vec::new();
+        };
+        let received = guest.receive().and_then(|messages| {
+            if guest.is_silent() {
+                return Err("Nothing heard from them for a while".to_string());
+            }
+            guest.keep_alive(&HostMessage::Ping)?;
+            Ok(messages)
+        });
+        let messages = match received {
+            Ok(messages) => messages,
+            Err(e) => {
+                println!("{} dropped out: {}", self.player.other(), e);
+                self.guest = None;
+                return Vec::new();
+            },
+        };
+
+        let mut events = Vec::new();
+        // Moving the highlight changes the guest's view without any events
+        let mut played = false;
+        for message in messages {
+            match message {
+                ClientMessage::Input(input) if is_game_input(&input) => {
+                    let new_events = self.st.update_as_other(&mut self.guest_mode, &input);
+                    let guest_played = PlayedInput::after(&input, &self.st, &new_events, indexer);
+                    self.recorded.push(RecordedStep::GuestInput(guest_played));
+                    events.extend(new_events);
+                    played = true;
+                },
+                ClientMessage::Input(input) => eprintln!("Ignoring {:?} from the guest", input),
+                ClientMessage::Hello { .. } | ClientMessage::Ping => (),
+            }
+        }
+        if played {
+            self.events.extend(events.iter().cloned());
+            self.send_update(&events, indexer);
+        }
+        events
+    }
+
+    fn accept(&mut self, indexer: &CardIndexer)
+    {
+        match self.listener.accept() {
+            Ok((stream, address)) => match Connection::<ClientMessage>::new(stream) {
+                Ok(connection) => {
+                    self.pending.push(Pending { connection, address, since: Instant::now() })
+                },
+                Err(e) => eprintln!("Problem accepting a connection: {}", e),
+            },
+            Err(e) if e.kind() == ErrorKind::WouldBlock => (),
+            Err(e) => eprintln!("Problem accepting a connection: {}", e),
+        }
+
+        for mut pending in std::mem::take(&mut self.pending) {
+            let hello = match pending.connection.receive() {
+                Ok(messages) => messages.into_iter().next(),
+                Err(e) => {
+                    eprintln!("Problem accepting a connection: {}", e);
+                    continue;
+                },
+            };
+            match hello {
+                Some(ClientMessage::Hello { version, game_id, cards_seen, events_seen })
+                    if version == NET_VERSION =>
+                {
+                    let Pending { connection, address, .. } = pending;
+                    self.admit(connection, address, game_id, cards_seen, events_seen, indexer);
+                },
+                Some(ClientMessage::Hello { version, .. }) => {
+                    let reason = format!(
+                        "The host speaks version {}, not version {}",
+                        NET_VERSION, version
+                    );
+                    eprintln!("Turned away a player: {}", reason);
+                    let refused = HostMessage::Refused { reason, try_again: false };
+                    let _ = pending.connection.send(&refused);
+                },
+                Some(message) => eprintln!("Expected a hello, got {:?}", message),
+                None if pending.since.elapsed() >= HELLO_TIMEOUT => {
+                    eprintln!("Problem accepting a connection: no hello from {}", pending.address)
+                },
+                None => self.pending.push(pending),
+            }
+        }
+    }
+
+    /// Lets in a connection that's said hello, if it's the right player.
+    fn admit(
+        &mut self,
+        mut connection: Connection<ClientMessage>,
+        address: SocketAddr,
+        game_id: Option<String>,
+        cards_seen: usize,
+        events_seen: usize,
+        indexer: &CardIndexer,
+    )
+    {
+        // A hello with the game's ID gets in even if the host still thinks
+        // they're connected, since that connection must have died
+        let refusal = match &game_id {
+            Some(id) if *id != self.game_id => {
+                Some((format!("There's no game {} here", id), false))
+            },
+            None if self.guest.is_some() => Some(("The game is full".to_string(), true)),
+            None if self.joined => Some((
+                format!("The game is waiting for {} to come back", self.player.other()),
+                false,
+            )),
+            _ => None,
+        };
+        if let Some((reason, try_again)) = refusal {
+            eprintln!("Turned away a player: {}", reason);
+            let _ = connection.send(&HostMessage::Refused { reason, try_again });
+            return;
+        }
+
+        if self.joined {
+            println!("{} is back, from {}", self.player.other(), address);
+        } else {
+            println!("{} joined from {}", self.player.other(), address);
+        }
+        let welcome = HostMessage::Welcome {
+            version: NET_VERSION,
+            player: self.player.other(),
+            game_id: self.game_id.clone(),
+        };
+        self.guest = Some(connection);
+        self.joined = true;
+        self.cards_sent = cards_seen.min(indexer.len());
+        self.send(&welcome);
+        let missed = self.events.get(events_seen..).unwrap_or_default().to_vec();
+        self.send_update(&missed, indexer);
+    }
+
+
+    fn send_update(&mut self, events: &[GameEvent], indexer: &CardIndexer)
+    {
+        if self.guest.is_none() {
+            return;
+        }
+        if self.cards_sent < indexer.len() {
+            self.send(&HostMessage::Cards(card_names(indexer, self.cards_sent)));
+            self.cards_sent = indexer.len();
+        }
+        let guest = self.player.other();
+        let view = PlayerView::make(&self.st, guest, &self.guest_mode);
+        self.send(&HostMessage::View(Box::new(view)));
+        if !events.is_empty() {
+            let seen = events.iter().map(|event| view::event_seen_by(guest, event)).collect();
+            self.send(&HostMessage::Events(seen));
+        }
+    }
+
+    fn send(&mut self, message: &HostMessage)
+    {
+        if let Some(guest) = &mut self.guest {
+            if let Err(e) = guest.send(message) {
+                println!("{} dropped out: {}", self.player.other(), e);
+                self.guest = None;
+            }
+        }
+    }
+}
+
+/// The joining side of a network game.
+pub struct Guest
+{
+    /// Looked up once when joining, so reconnecting doesn't wait on DNS.
+    address: SocketAddr,
+    host: Option<Connection<HostMessage>>,
+    /// A connection being made on another thread, while reconnecting.
+    connecting: Option<Receiver<Result<Connection<HostMessage>, String>>>,
+    player: Player,
+    game_id: String,
+    view: PlayerView,
+    views_received: usize,
+    events_seen: usize,
+    /// Why the connection dropped, while trying to get it back. It stays set
+    /// after reconnecting until the host's welcome arrives.
+    outage: Option<String>,
+    next_try: Instant,
+}
+
+impl Guest
+{
+    /// `game_id` rejoins a game after a restart.
+    pub fn join(address: &str, game_id: Option<String>) -> Result<Self, String>
+    {
+        let hello =
+            ClientMessage::Hello { version: NET_VERSION, game_id, cards_seen: 0, events_seen: 0 };
+        let socket = address
+            .to_socket_addrs()
+            .map_err(|e| format!("Couldn't find {}: {}", address, e))?
+            .next()
+            .ok_or_else(|| format!("Couldn't find {}", address))?;
+        let mut host = Guest::connect(socket, &hello, HELLO_TIMEOUT)?;
+        let (player, game_id) = match host.receive_within(HELLO_TIMEOUT)? {
+            HostMessage::Welcome { player, game_id, .. } => (player, game_id),
+            HostMessage::Refused { reason, .. } => {
+                return Err(format!("The host said no: {}", reason))
+            },
+            message => return Err(format!("Expected a welcome, got {:?}", message)),
+        };
+        // An empty board until the host sends the real one
+        let mut st = State::make(Vec::new(), Vec::new(), 0);
+        st.start_on(player);
+        Ok(Guest {
+            address: socket,
+            host: Some(host),
+            connecting: None,
+            player,
+            game_id,
+            view: PlayerView::of(&st),
+            views_received: 0,
+            events_seen: 0,
+            outage: None,
+            next_try: Instant::now(),
+        })
+    }
+
+    /// Connects and says hello, without waiting for the answer.
+    fn connect(
+        address: SocketAddr,
+        hello: &ClientMessage,
+        timeout: Duration,
+    ) -> Result<Connection<HostMessage>, String>
+    {
+        let stream = TcpStream::connect_timeout(&address, timeout)
+            .map_err(|e| format!("Couldn't connect to {}: {}", address, e))?;
+        let mut host = Connection::<HostMessage>::new(stream)?;
+        host.send(hello)?;
+        Ok(host)
+    }
+
+    /// The game as of the last view the host sent.
+    pub fn view(&self) -> &PlayerView
+    {
+        &self.view
+    }
+
+    /// How many views the host has sent, to tell when there's a new one.
+    pub fn views_received(&self) -> usize
+    {
+        self.views_received
+    }
+
+    pub fn player(&self) -> Player
+    {
+        self.player
+    }
+
+    pub fn game_id(&self) -> &str
+    {
+        &self.game_id
+    }
+
+    /// Why the connection dropped, while reconnecting.
+    pub fn outage(&self) -> Option<&str>
+    {
+        self.outage.as_deref()
+    }
+
+    /// Only game inputs are sent, and nothing while reconnecting.
+    pub fn send(&mut self, input: &Input)
+    {
+        let Some(host) = &mut self.host else {
+            return;
+        };
+        if is_game_input(input) {
+            if let Err(e) = host.send(&ClientMessage::Input(input.clone())) {
+                self.lose_host(e);
+            }
+        }
+    }
+
+    /// Catches up with the host, reconnecting if the connection dropped.
+    /// New cards are added to the indexer and returned, so their metadata
+    /// and images can be loaded. An error means the host turned us away.
+    pub fn poll(
+        &mut self,
+        indexer: &mut CardIndexer,
+    ) -> Result<(Vec<GameEvent>, Vec<usize>), String>
+    {
+        if self.host.is_none() {
+            self.reconnect(indexer);
+        }
+        let Some(host) = &mut self.host else {
+            return Ok((Vec::new(), Vec::new()));
+        };
+        let received = host.receive().and_then(|messages| {
+            if host.is_silent() {
+                return Err("Nothing heard from the host for a while".to_string());
+            }
+            host.keep_alive(&ClientMessage::Ping)?;
+            Ok(messages)
+        });
+        let messages = match received {
+            Ok(messages) => messages,
+            Err(e) => {
+                self.lose_host(e);
+                return Ok((Vec::new(), Vec::new()));
+            },
+        };
+
+        let mut events = Vec::new();
+        let mut new_cards = Vec::new();
+        for message in messages {
+            match message {
+                HostMessage::Cards(names) => new_cards.extend(intern_cards(indexer, &names)?),
+                HostMessage::View(view) => {
+                    self.view = *view;
+                    self.views_received += 1;
+                },
+                HostMessage::Events(new_events) => {
+                    self.events_seen += new_events.len();
+                    events.extend(new_events);
+                },
+                HostMessage::Welcome { .. } => {
+                    println!("Back in game {}", self.game_id);
+                    self.outage = None;
+                },
+                HostMessage::Refused { reason, try_again: true } => {
+                    self.lose_host(reason);
+                    self.next_try = Instant::now() + RECONNECT_INTERVAL;
+                    break;
+                },
+                HostMessage::Refused { reason, .. } => {
+                    return Err(format!("The host said no: {}", reason))
+                },
+                HostMessage::Ping => (),
+            }
+        }
+        Ok((events, new_cards))
+    }
+
+    fn lose_host(&mut self, reason: String)
+    {
+        eprintln!("Lost the host, reconnecting: {}", reason);
+        self.host = None;
+        self.outage = Some(reason);
+        self.next_try = Instant::now();
+    }
+
+    /// Says hello again, connecting on another thread. The welcome is
+    /// picked up by `poll` like any other message, so the window isn't held
+    /// up waiting for either.
+    fn reconnect(&mut self, indexer: &CardIndexer)
+    {
+        if let Some(connecting) = &self.connecting {
+            let connected = match connecting.try_recv() {
+                Ok(connected) => connected,
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => Err("Couldn't connect".to_string()),
+            };
+            self.connecting = None;
+            match connected {
+                Ok(host) => self.host = Some(host),
+                Err(e) => {
+                    self.outage = Some(e);
+                    self.next_try = Instant::now() + RECONNECT_INTERVAL;
+                },
+            }
+            return;
+        }
+        if Instant::now() < self.next_try {
+            return;
+        }
+        let hello = ClientMessage::Hello {
+            version: NET_VERSION,
+            game_id: Some(self.game_id.clone()),
+            cards_seen: indexer.len(),
+            events_seen: self.events_seen,
+        };
+        let address = self.address;
+        let (sender, connecting) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = sender.send(Guest::connect(address, &hello, RECONNECT_TIMEOUT));
+        });
+        self.connecting = Some(connecting);
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::replay::{Recording, Replay};
+    use crate::state::Card;
+
+    /// Ten cards, the first five in player 1's deck and the rest in player
+    /// 2's, hosted on a free port.
+    fn host() -> (Host, CardIndexer)
+    {
+        let mut indexer = CardIndexer::default();
+        for i in 0..10 {
+            indexer.intern("ds", &format!("card-{}", i));
+        }
+        let st = State::make((0..5).collect(), (5..10).collect(), 0);
+        (Host::new(st, 0).unwrap(), indexer)
+    }
+
+    fn join(
+        host: &mut Host,
+        indexer: &CardIndexer,
+        game_id: Option<String>,
+    ) -> Result<Guest, String>
+    {
+        let address = format!("127.0.0.1:{}", host.port());
+        let joining = thread::spawn(move || Guest::join(&address, game_id));
+        while !joining.is_finished() {
+            host.poll(indexer);
+            thread::sleep(Duration::from_millis(5));
+        }
+        joining.join().unwrap()
+    }
+
+    /// Lets both sides poll for a while, returning the events and new cards
+    /// the guest got.
+    fn exchange(
+        host: &mut Host,
+        indexer: &CardIndexer,
+        guest: &mut Guest,
+        guest_indexer: &mut CardIndexer,
+    ) -> (Vec<GameEvent>, Vec<usize>)
+    {
+        let (mut events, mut new_cards) = (Vec::new(), Vec::new());
+        for _ in 0..50 {
+            host.poll(indexer);
+            let (more_events, more_cards) = guest.poll(guest_indexer).unwrap();
+            events.extend(more_events);
+            new_cards.extend(more_cards);
+            thread::sleep(Duration::from_millis(5));
+        }
+        (events, new_cards)
+    }
+
+    /// The card each `CardMoved` names, in order.
+    fn cards_moved(events: &[GameEvent]) -> Vec<Option<Card>>
+    {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                GameEvent::CardMoved { card, .. } => Some(*card),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn the_guest_only_sees_their_own_draws()
+    {
+        let (mut host, indexer) = host();
+        let mut guest = join(&mut host, &indexer, None).unwrap();
+        assert_eq!(guest.player(), Player::Player2);
+        let mut guest_indexer = CardIndexer::default();
+        let (_, new_cards) = exchange(&mut host, &indexer, &mut guest, &mut guest_indexer);
+        assert_eq!(new_cards.len(), 10);
+
+        host.play(&Input::Deck, &indexer);
+        let drawn = host.play(&Input::Deck, &indexer);
+        assert_eq!(cards_moved(&drawn), vec![Some(4)]);
+        guest.send(&Input::Deck);
+        guest.send(&Input::Deck);
+        let (events, _) = exchange(&mut host, &indexer, &mut guest, &mut guest_indexer);
+        assert_eq!(cards_moved(&events), vec![None, Some(9)]);
+        assert_eq!(guest.view().player2.hand, vec![Some(9)]);
+        assert_eq!(guest.view().player1.hand, vec![None]);
+    }
+
+    #[test]
+    fn the_host_records_both_players_inputs()
+    {
+        let (mut host, mut indexer) = host();
+        let mut recording = Recording::start(host.state(), &indexer, 0);
+        let mut guest = join(&mut host, &indexer, None).unwrap();
+        let mut guest_indexer = CardIndexer::default();
+        host.play(&Input::Deck, &indexer);
+        host.play(&Input::Deck, &indexer);
+        for input in [Input::Deck, Input::Shuffle, Input::Deck] {
+            guest.send(&input);
+        }
+        exchange(&mut host, &indexer, &mut guest, &mut guest_indexer);
+
+        recording.steps = host.take_recorded();
+        assert_eq!(recording.steps.len(), 5);
+        assert!(host.take_recorded().is_empty());
+        let mut replay = Replay::make(&recording, &mut indexer).unwrap();
+        assert!(replay.warnings.is_empty(), "{:?}", replay.warnings);
+        replay.seek(replay.steps());
+        let (played, replayed) = (PlayerView::of(host.state()), replay.view());
+        assert_eq!(replayed.player1.hand, played.player1.hand);
+        assert_eq!(replayed.player2.hand, vec![None]);
+        assert_eq!(replayed.player2.deck_size, played.player2.deck_size);
+    }
+
+    #[test]
+    fn a_dropped_guest_gets_what_they_missed_once()
+    {
+        let (mut host, indexer) = host();
+        let mut guest = join(&mut host, &indexer, None).unwrap();
+        let mut guest_indexer = CardIndexer::default();
+        exchange(&mut host, &indexer, &mut guest, &mut guest_indexer);
+        host.play(&Input::Deck, &indexer);
+
+        guest.lose_host("Unplugged".to_string());
+        // Played before the host notices they've gone
+        host.play(&Input::Deck, &indexer);
+        let (events, new_cards) = exchange(&mut host, &indexer, &mut guest, &mut guest_indexer);
+        assert_eq!(guest.outage(), None);
+        assert!(host.is_connected());
+        assert_eq!(cards_moved(&events), vec![None]);
+        // They already had every card
+        assert!(new_cards.is_empty());
+        assert_eq!(guest.view().player1.hand, vec![None]);
+    }
+
+    #[test]
+    fn rejoining_takes_over_a_stale_connection()
+    {
+        let (mut host, indexer) = host();
+        let mut stale = join(&mut host, &indexer, None).unwrap();
+        let game_id = host.game_id().to_string();
+        let mut guest = join(&mut host, &indexer, Some(game_id)).unwrap();
+        assert!(host.is_connected());
+
+        let mut guest_indexer = CardIndexer::default();
+        exchange(&mut host, &indexer, &mut guest, &mut guest_indexer);
+        assert_eq!(guest.outage(), None);
+        // The host closed the old connection, after what it had sent
+        let mut stale_indexer = CardIndexer::default();
+        for _ in 0..3 {
+            stale.poll(&mut stale_indexer).unwrap();
+        }
+        assert!(stale.outage().is_some());
+    }
+
+    #[test]
+    fn wrong_game_ids_and_newcomers_are_turned_away()
+    {
+        let (mut host, indexer) = host();
+        let error = join(&mut host, &indexer, Some("nope".to_string())).err().unwrap();
+        assert!(error.contains("There's no game nope here"), "{}", error);
+
+        let _guest = join(&mut host, &indexer, None).unwrap();
+        let error = join(&mut host, &indexer, None).err().unwrap();
+        assert!(error.contains("The game is full"), "{}", error);
+    }
+}
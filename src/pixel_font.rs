@@ -0,0 +1,170 @@
+//! A tiny 3x5 bitmap font drawn with `fill_rect`, same as the dice, so text
+//! doesn't need SDL_ttf or a font file.
+
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+
+type Renderer = sdl2::render::Canvas<sdl2::video::Window>;
+
+pub const GLYPH_WIDTH: u32 = 3;
+pub const GLYPH_HEIGHT: u32 = 5;
+/// Horizontal distance between the starts of two characters, in font pixels.
+pub const GLYPH_ADVANCE: u32 = GLYPH_WIDTH + 1;
+/// Vertical distance between the starts of two lines, in font pixels.
+pub const LINE_ADVANCE: u32 = GLYPH_HEIGHT + 2;
+
+/// Each row is 3 bits, most significant bit on the left.
+fn glyph(c: char) -> [u8; 5]
+{
+    match c.to_ascii_uppercase() {
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        'É' | 'é' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b110, 0b001, 0b010, 0b100, 0b111],
+        '3' => [0b110, 0b001, 0b010, 0b001, 0b110],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b110, 0b001, 0b110],
+        '6' => [0b011, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b110],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        ';' => [0b000, 0b010, 0b000, 0b010, 0b100],
+        '!' => [0b010, 0b010, 0b010, 0b000, 0b010],
+        '?' => [0b110, 0b001, 0b010, 0b000, 0b010],
+        '\'' => [0b010, 0b010, 0b000, 0b000, 0b000],
+        '"' => [0b101, 0b101, 0b000, 0b000, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '(' => [0b001, 0b010, 0b010, 0b010, 0b001],
+        ')' => [0b100, 0b010, 0b010, 0b010, 0b100],
+        '[' => [0b011, 0b010, 0b010, 0b010, 0b011],
+        ']' => [0b110, 0b010, 0b010, 0b010, 0b110],
+        '<' => [0b001, 0b010, 0b100, 0b010, 0b001],
+        '>' => [0b100, 0b010, 0b001, 0b010, 0b100],
+        '#' => [0b101, 0b111, 0b101, 0b111, 0b101],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        '*' => [0b000, 0b101, 0b010, 0b101, 0b000],
+        ' ' => [0b000; 5],
+        // Anything we don't have a glyph for
+        _ => [0b111, 0b101, 0b101, 0b101, 0b111],
+    }
+}
+
+/// Width in screen pixels of `text` drawn at `scale`.
+pub fn text_width(text: &str, scale: u32) -> u32
+{
+    let len = text.chars().count() as u32;
+    if len == 0 {
+        0
+    } else {
+        (len * GLYPH_ADVANCE - 1) * scale
+    }
+}
+
+pub fn draw_text(
+    canvas: &mut Renderer,
+    x: i32,
+    y: i32,
+    scale: u32,
+    color: Color,
+    text: &str,
+) -> Result<(), String>
+{
+    canvas.set_draw_color(color);
+    for (i, c) in text.chars().enumerate() {
+        let glyph_x = x + (i as u32 * GLYPH_ADVANCE * scale) as i32;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits & (0b100 >> column) != 0 {
+                    let px = glyph_x + (column * scale) as i32;
+                    let py = y + (row as u32 * scale) as i32;
+                    canvas.fill_rect(Rect::new(px, py, scale, scale))?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Splits `text` into lines of at most `max_chars`, breaking between words
+/// where possible.
+pub fn wrap(text: &str, max_chars: usize) -> Vec<String>
+{
+    let max_chars = max_chars.max(1);
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let line_len = line.chars().count();
+        let word_len = word.chars().count();
+        if line_len > 0 && line_len + 1 + word_len <= max_chars {
+            line.push(' ');
+            line.push_str(word);
+            continue;
+        }
+        if line_len > 0 {
+            lines.push(std::mem::take(&mut line));
+        }
+        let mut chars: Vec<char> = word.chars().collect();
+        while chars.len() > max_chars {
+            lines.push(chars.drain(..max_chars).collect());
+        }
+        line = chars.into_iter().collect();
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Draws wrapped text inside a box `max_width` pixels wide, returning how
+/// many pixels tall it came out.
+pub fn draw_wrapped_text(
+    canvas: &mut Renderer,
+    x: i32,
+    y: i32,
+    max_width: u32,
+    scale: u32,
+    color: Color,
+    text: &str,
+) -> Result<u32, String>
+{
+    let max_chars = ((max_width / scale + 1) / GLYPH_ADVANCE) as usize;
+    let lines = wrap(text, max_chars);
+    for (i, line) in lines.iter().enumerate() {
+        let line_y = y + (i as u32 * LINE_ADVANCE * scale) as i32;
+        draw_text(canvas, x, line_y, scale, color, line)?;
+    }
+    Ok(lines.len() as u32 * LINE_ADVANCE * scale)
+}
@@ -0,0 +1,687 @@
+//! Recorded games. A recording is the board just after setup plus every
+//! input that reached the game (including undo, redo and loading a save), in
+//! the same JSON style as `save`:
+//!
+//! ```json
+//! {
+//!   "version": 3,
+//!   "undo_depth": 200,
+//!   "start": { ...a save... },
+//!   "steps": [
+//!     { "Input": { "input": "Deck", "rng_position": "86", "outcome": null } },
+//!     { "Input": { "input": { "Slot": 2 }, "rng_position": "86", "outcome": null } },
+//!     { "GuestInput": { "input": "Roll", "rng_position": "87", "outcome": { "Rolled": 4 } } },
+//!     { "Loaded": { ...a save... } }
+//!   ]
+//! }
+//! ```
+//!
+//! Rolls and shuffles are recorded as they came out (a shuffle as the whole
+//! deck afterwards, `{ "Shuffled": { "player": "Player1", "deck": [...] } }`)
+//! and replayed that way, so a replay shows what happened even if the RNG or
+//! the way it's used has changed since. The start still carries the seed,
+//! and each step notes how far into the RNG the game was after it, which is
+//! how a replay notices it has gone differently (say the rules for an input
+//! changed since the recording was made).
+//!
+//! `GuestInput`s are from the player who joined a network game, played in
+//! their own input mode. That player never has the whole game to record, so
+//! they record what they saw instead: each view the host sent them, with
+//! the events that came with it, and the cards in the order the views'
+//! indices go:
+//!
+//! ```json
+//! {
+//!   "version": 3,
+//!   "seen_by": "Player2",
+//!   "cards": ["mt/pachirisu-mt-86", "..."],
+//!   "steps": [ { "view": { ... }, "events": [ ... ] } ]
+//! }
+//! ```
+//!
+//! A turn starts at the beginning and every time play switches sides.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::game_log::{GameEvent, GameLog};
+use crate::history::History;
+use crate::input::Input;
+use crate::load_cards::CardIndexer;
+use crate::net::{card_names, intern_cards};
+use crate::save::{pile_from_names, pile_names, SavedGame};
+use crate::state::{Player, State, UIAlert};
+use crate::view::PlayerView;
+
+pub const REPLAY_VERSION: u32 = 3;
+
+/// How a roll or shuffle came out.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RecordedOutcome
+{
+    Rolled(u8),
+    /// The whole deck afterwards, bottom first.
+    Shuffled
+    {
+        player: Player, deck: Vec<String>
+    },
+}
+
+impl RecordedOutcome
+{
+    /// The roll or shuffle among `events`, if there was one.
+    fn of(events: &[GameEvent], st: &State, indexer: &CardIndexer) -> Option<Self>
+    {
+        events.iter().find_map(|event| match event {
+            GameEvent::DieRolled { value, .. } => Some(RecordedOutcome::Rolled(*value)),
+            GameEvent::DeckShuffled { player } => Some(RecordedOutcome::Shuffled {
+                player: *player,
+                deck: pile_names(&st.layout(*player).deck, indexer),
+            }),
+            _ => None,
+        })
+    }
+
+    /// Makes the same roll or shuffle among `events` come out this way.
+    /// False if there isn't one, or the deck has different cards in it.
+    fn apply(
+        &self,
+        st: &mut State,
+        events: &mut [GameEvent],
+        indexer: &mut CardIndexer,
+    ) -> Result<bool, String>
+    {
+        for event in events.iter_mut() {
+            match (self, event) {
+                (RecordedOutcome::Rolled(rolled), GameEvent::DieRolled { value, .. }) => {
+                    *value = *rolled;
+                    st.ui_alert = Some(UIAlert::Roll(*rolled));
+                    return Ok(true);
+                },
+                (
+                    RecordedOutcome::Shuffled { player, deck },
+                    GameEvent::DeckShuffled { player: shuffled },
+                ) if player == shuffled => {
+                    let recorded = pile_from_names(deck, indexer)?;
+                    let layout = match player {
+                        Player::Player1 => &mut st.player1_layout,
+                        Player::Player2 => &mut st.player2_layout,
+                    };
+                    let (mut before, mut after) = (layout.deck.clone(), recorded.clone());
+                    before.sort();
+                    after.sort();
+                    if before != after {
+                        return Ok(false);
+                    }
+                    layout.deck = recorded;
+                    return Ok(true);
+                },
+                _ => (),
+            }
+        }
+        Ok(false)
+    }
+}
+
+/// An input as it was played.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlayedInput
+{
+    pub input: Input,
+    /// How far into the RNG the game was afterwards.
+    pub rng_position: String,
+    pub outcome: Option<RecordedOutcome>,
+}
+
+impl PlayedInput
+{
+    /// Call after playing `input`, with the resulting state and the events
+    /// it caused.
+    pub fn after(input: &Input, st: &State, events: &[GameEvent], indexer: &CardIndexer) -> Self
+    {
+        PlayedInput {
+            input: input.clone(),
+            rng_position: st.rng_position().to_string(),
+            outcome: RecordedOutcome::of(events, st, indexer),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum RecordedStep
+{
+    Input(PlayedInput),
+    /// From the player who joined a network game, in their own input mode
+    /// (see `State::update_as_other`).
+    GuestInput(PlayedInput),
+    Loaded(Box<SavedGame>),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Recording
+{
+    pub version: u32,
+    pub undo_depth: usize,
+    pub start: SavedGame,
+    pub steps: Vec<RecordedStep>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SeenStep
+{
+    pub view: PlayerView,
+    pub events: Vec<GameEvent>,
+}
+
+/// What the player who joined a network game saw of it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SeenRecording
+{
+    pub version: u32,
+    pub seen_by: Player,
+    /// `"<set>/<card>"` for each card index the views use, in order.
+    pub cards: Vec<String>,
+    pub steps: Vec<SeenStep>,
+}
+
+/// A recording read from a file, of either kind.
+pub enum Recorded
+{
+    Game(Box<Recording>),
+    Seen(SeenRecording),
+}
+
+/// Plays `input` the same way live and in replays: undo and redo go to the
+/// history, everything else to `State::update`. False for an undo or redo
+/// with nothing to go back to.
+pub fn play_input(st: &mut State, history: &mut History, log: &mut GameLog, input: &Input) -> bool
+{
+    match input {
+        Input::Undo => {
+            let undone = history.undo(st);
+            if undone {
+                log.push(GameEvent::Undo);
+            }
+            undone
+        },
+        Input::Redo => {
+            let redone = history.redo(st);
+            if redone {
+                log.push(GameEvent::Redo);
+            }
+            redone
+        },
+        _ => {
+            log.extend(history.update(st, input));
+            true
+        },
+    }
+}
+
+impl Recording
+{
+    /// Starts recording from `st`, which should be fresh from `State::setup`.
+    pub fn start(st: &State, indexer: &CardIndexer, undo_depth: usize) -> Self
+    {
+        Recording {
+            version: REPLAY_VERSION,
+            undo_depth,
+            start: SavedGame::from_state(st, indexer),
+            steps: Vec::new(),
+        }
+    }
+
+    /// Call after `play_input`, with the resulting state and the events the
+    /// input caused.
+    pub fn record_input(
+        &mut self,
+        input: &Input,
+        st: &State,
+        events: &[GameEvent],
+        indexer: &CardIndexer,
+    )
+    {
+        self.steps.push(RecordedStep::Input(PlayedInput::after(input, st, events, indexer)));
+    }
+
+    pub fn record_load(&mut self, st: &State, indexer: &CardIndexer)
+    {
+        let saved = SavedGame::from_state(st, indexer);
+        self.steps.push(RecordedStep::Loaded(Box::new(saved)));
+    }
+}
+
+impl SeenRecording
+{
+    pub fn start(seen_by: Player) -> Self
+    {
+        SeenRecording { version: REPLAY_VERSION, seen_by, cards: Vec::new(), steps: Vec::new() }
+    }
+
+    /// `indexer` is the one the view's cards are indices into.
+    pub fn record(&mut self, view: &PlayerView, events: &[GameEvent], indexer: &CardIndexer)
+    {
+        self.cards.extend(card_names(indexer, self.cards.len()));
+        self.steps.push(SeenStep { view: view.clone(), events: events.to_vec() });
+    }
+}
+
+/// Writes a `Recording` or a `SeenRecording`.
+pub fn write(recording: &impl Serialize, path: &Path) -> Result<(), String>
+{
+    let json = serde_json::to_string(recording).map_err(|e| e.to_string())?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Problem creating {:?}: {}", dir, e))?;
+    }
+    fs::write(path, json).map_err(|e| format!("Problem writing {:?}: {}", path, e))
+}
+
+pub fn read(path: &Path) -> Result<Recorded, String>
+{
+    let contents =
+        fs::read_to_string(path).map_err(|e| format!("Problem reading {:?}: {}", path, e))?;
+    let parse_error = |e: serde_json::Error| format!("Problem parsing {:?}: {}", path, e);
+    let json: serde_json::Value = serde_json::from_str(&contents).map_err(parse_error)?;
+    match json["version"].as_u64() {
+        Some(version) if version == u64::from(REPLAY_VERSION) => (),
+        Some(version) => {
+            return Err(format!(
+                "{:?} is a version {} recording, this version reads version {}",
+                path, version, REPLAY_VERSION
+            ))
+        },
+        None => return Err(format!("{:?} isn't a recording", path)),
+    }
+    // Only recordings of what a player saw say who saw it
+    if json.get("seen_by").is_some() {
+        serde_json::from_value(json).map(Recorded::Seen).map_err(parse_error)
+    } else {
+        serde_json::from_value(json).map(|game| Recorded::Game(Box::new(game))).map_err(parse_error)
+    }
+}
+
+/// Every view a recording went through, to step around in.
+pub struct Replay
+{
+    // views[0] is the start, views[i] is after step i
+    views: Vec<PlayerView>,
+    // How many log events had happened by each view
+    event_counts: Vec<usize>,
+    full_log: GameLog,
+    /// Indices into the views where each turn starts.
+    turn_starts: Vec<usize>,
+    position: usize,
+    /// Steps where the replay didn't match the recording.
+    pub warnings: Vec<String>,
+    /// Events up to the current position.
+    pub log: GameLog,
+}
+
+impl Replay
+{
+    /// Plays the whole recording through. Cards the indexer doesn't know yet
+    /// are added to it.
+    pub fn make(recording: &Recording, indexer: &mut CardIndexer) -> Result<Self, String>
+    {
+        let mut st = recording.start.to_state(indexer)?;
+        let mut history = History::new(recording.undo_depth);
+        // The joining player's, in a network game
+        let mut guest_mode = st.input_mode_of(st.currently_viewing.other());
+        let mut replay = Replay::starting_at(PlayerView::of(&st), Vec::new());
+
+        for (i, step) in recording.steps.iter().enumerate() {
+            let events = match step {
+                RecordedStep::Input(played) => {
+                    let mut step_log = GameLog::default();
+                    play_input(&mut st, &mut history, &mut step_log, &played.input);
+                    let mut events = step_log.events().to_vec();
+                    replay.follow(i + 1, played, &mut st, &mut events, indexer)?;
+                    events
+                },
+                RecordedStep::GuestInput(played) => {
+                    let mut events = st.update_as_other(&mut guest_mode, &played.input);
+                    replay.follow(i + 1, played, &mut st, &mut events, indexer)?;
+                    events
+                },
+                RecordedStep::Loaded(saved) => {
+                    let loaded = saved.to_state(indexer)?;
+                    history.record(std::mem::replace(&mut st, loaded));
+                    Vec::new()
+                },
+            };
+            replay.push(PlayerView::of(&st), events);
+        }
+        Ok(replay)
+    }
+
+    /// The recording's cards are added to the indexer, which has to be
+    /// empty so they get the indices the views use.
+    pub fn seen(recording: &SeenRecording, indexer: &mut CardIndexer) -> Result<Self, String>
+    {
+        let cards = intern_cards(indexer, &recording.cards)?;
+        if cards.iter().enumerate().any(|(i, card)| i != *card) {
+            return Err("The recording's cards have to be the first ones loaded".to_string());
+        }
+        let Some((first, rest)) = recording.steps.split_first() else {
+            return Err("Nothing was recorded".to_string());
+        };
+        let mut replay = Replay::starting_at(first.view.clone(), first.events.clone());
+        for step in rest {
+            replay.push(step.view.clone(), step.events.clone());
+        }
+        replay.seek(0);
+        Ok(replay)
+    }
+
+    fn starting_at(view: PlayerView, events: Vec<GameEvent>) -> Self
+    {
+        let mut full_log = GameLog::default();
+        full_log.extend(events);
+        Replay {
+            views: vec![view],
+            event_counts: vec![full_log.len()],
+            full_log,
+            turn_starts: vec![0],
+            position: 0,
+            warnings: Vec::new(),
+            log: GameLog::default(),
+        }
+    }
+
+    /// Adds the view after a step, and the events of the step.
+    fn push(&mut self, view: PlayerView, events: Vec<GameEvent>)
+    {
+        let switched_sides =
+            events.iter().any(|event| matches!(event, GameEvent::SidesSwitched { .. }));
+        self.full_log.extend(events);
+        self.views.push(view);
+        self.event_counts.push(self.full_log.len());
+        if switched_sides {
+            self.turn_starts.push(self.views.len() - 1);
+        }
+    }
+
+    /// Makes a replayed input's roll or shuffle come out as recorded, and
+    /// notes anything that doesn't match the recording. `step` counts from 1.
+    fn follow(
+        &mut self,
+        step: usize,
+        played: &PlayedInput,
+        st: &mut State,
+        events: &mut [GameEvent],
+        indexer: &mut CardIndexer,
+    ) -> Result<(), String>
+    {
+        let followed = match &played.outcome {
+            Some(outcome) => outcome.apply(st, events, indexer)?,
+            None => RecordedOutcome::of(events, st, indexer).is_none(),
+        };
+        if !followed {
+            self.warnings.push(format!("Step {}: didn't roll or shuffle like the recording", step));
+        }
+        if st.rng_position().to_string() != played.rng_position {
+            self.warnings.push(format!(
+                "Step {}: random numbers used went to {} instead of {}",
+                step,
+                st.rng_position(),
+                played.rng_position
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn view(&self) -> &PlayerView
+    {
+        &self.views[self.position]
+    }
+
+    /// 0 is the start, `steps()` is the end.
+    pub fn position(&self) -> usize
+    {
+        self.position
+    }
+
+    pub fn steps(&self) -> usize
+    {
+        self.views.len() - 1
+    }
+
+    /// Counting from 1.
+    pub fn turn(&self) -> usize
+    {
+        self.turn_starts.iter().filter(|start| **start <= self.position).count()
+    }
+
+    pub fn turns(&self) -> usize
+    {
+        self.turn_starts.len()
+    }
+
+    pub fn seek(&mut self, position: usize)
+    {
+        self.position = position.min(self.steps());
+        let events = &self.full_log.events()[..self.event_counts[self.position]];
+        self.log = GameLog::default();
+        self.log.extend(events.iter().cloned());
+    }
+
+    pub fn step_forward(&mut self)
+    {
+        self.seek(self.position + 1);
+    }
+
+    pub fn step_back(&mut self)
+    {
+        self.seek(self.position.saturating_sub(1));
+    }
+
+    /// Counting from 1; past the last turn goes to the last one.
+    pub fn jump_to_turn(&mut self, turn: usize)
+    {
+        let index = turn.clamp(1, self.turns()) - 1;
+        self.seek(self.turn_starts[index]);
+    }
+
+    pub fn next_turn(&mut self)
+    {
+        match self.turn_starts.iter().find(|start| **start > self.position) {
+            Some(start) => self.seek(*start),
+            None => self.seek(self.steps()),
+        }
+    }
+
+    /// To the start of this turn, or the one before if already there.
+    pub fn previous_turn(&mut self)
+    {
+        let start = self.turn_starts.iter().rev().find(|start| **start < self.position);
+        self.seek(start.copied().unwrap_or(0));
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::state::Card;
+
+    /// A game set up from twenty cards, ten in each deck.
+    fn game() -> (State, CardIndexer)
+    {
+        let mut indexer = CardIndexer::default();
+        let mut deck = |from: usize| -> Vec<Card> {
+            (from..from + 10).map(|i| indexer.intern("ds", &format!("card-{}", i))).collect()
+        };
+        let (deck1, deck2) = (deck(0), deck(10));
+        let mut st = State::make(deck1, deck2, 1234);
+        st.setup();
+        (st, indexer)
+    }
+
+    /// Plays `inputs` the way `main` does, recording them.
+    fn record(st: &mut State, indexer: &CardIndexer, inputs: &[Input]) -> (Recording, GameLog)
+    {
+        let mut recording = Recording::start(st, indexer, 10);
+        let mut history = History::new(10);
+        let mut log = GameLog::default();
+        for input in inputs {
+            let logged = log.len();
+            if play_input(st, &mut history, &mut log, input) {
+                recording.record_input(input, st, &log.events()[logged..], indexer);
+            }
+        }
+        (recording, log)
+    }
+
+    /// Views don't compare, but their JSON does.
+    fn json(view: &PlayerView) -> serde_json::Value
+    {
+        serde_json::to_value(view).unwrap()
+    }
+
+    #[test]
+    fn replays_end_up_where_the_game_did()
+    {
+        use Input as I;
+        let (mut st, mut indexer) = game();
+        let inputs = [
+            I::Roll,
+            I::Deck,
+            I::Deck,
+            I::Shuffle,
+            I::Undo,
+            I::Redo,
+            I::Cancel,
+            I::SwitchSides,
+            I::Deck,
+            I::Deck,
+            I::Undo,
+        ];
+        let (recording, log) = record(&mut st, &indexer, &inputs);
+
+        let mut replay = Replay::make(&recording, &mut indexer).unwrap();
+        assert!(replay.warnings.is_empty(), "{:?}", replay.warnings);
+        assert_eq!(replay.steps(), inputs.len());
+        assert!(replay.log.is_empty());
+        replay.seek(replay.steps());
+        assert_eq!(json(replay.view()), json(&PlayerView::of(&st)));
+        assert_eq!(replay.log.events(), log.events());
+    }
+
+    #[test]
+    fn rolls_and_shuffles_come_out_as_recorded()
+    {
+        use Input as I;
+        let (mut st, mut indexer) = game();
+        // Ends up searching the deck, so the view shows its order
+        let inputs = [I::Roll, I::Deck, I::Shuffle, I::Select];
+        let (mut recording, _) = record(&mut st, &indexer, &inputs);
+
+        // As if the RNG gave something else when replaying
+        let (mut rolled, mut deck) = (0, Vec::new());
+        for step in recording.steps.iter_mut() {
+            if let RecordedStep::Input(PlayedInput { outcome: Some(outcome), .. }) = step {
+                match outcome {
+                    RecordedOutcome::Rolled(value) => {
+                        *value = *value % 6 + 1;
+                        rolled = *value;
+                    },
+                    RecordedOutcome::Shuffled { deck: shuffled, .. } => {
+                        shuffled.reverse();
+                        deck = pile_from_names(shuffled, &mut indexer).unwrap();
+                    },
+                }
+            }
+        }
+        assert_eq!(deck.len(), 4);
+
+        let mut replay = Replay::make(&recording, &mut indexer).unwrap();
+        assert!(replay.warnings.is_empty(), "{:?}", replay.warnings);
+        replay.seek(1);
+        let roll = GameEvent::DieRolled { player: Player::Player1, value: rolled };
+        assert_eq!(replay.log.events(), [roll]);
+        assert!(matches!(replay.view().ui_alert, Some(UIAlert::Roll(value)) if value == rolled));
+        replay.seek(replay.steps());
+        assert_eq!(replay.view().deck_shown, deck);
+    }
+
+    #[test]
+    fn shuffles_that_dont_match_are_noted()
+    {
+        use Input as I;
+        let (mut st, mut indexer) = game();
+        let (mut recording, _) = record(&mut st, &indexer, &[I::Deck, I::Shuffle]);
+        let Some(RecordedStep::Input(played)) = recording.steps.last_mut() else {
+            panic!("{:?}", recording.steps);
+        };
+        let deck = vec!["ds/card-10".to_string()];
+        played.outcome = Some(RecordedOutcome::Shuffled { player: Player::Player1, deck });
+
+        let replay = Replay::make(&recording, &mut indexer).unwrap();
+        assert_eq!(replay.warnings.len(), 1);
+    }
+
+    #[test]
+    fn turns_start_when_play_switches_sides()
+    {
+        use Input as I;
+        let (mut st, mut indexer) = game();
+        let turn = [I::Deck, I::Deck, I::Cancel, I::SwitchSides];
+        let (recording, _) = record(&mut st, &indexer, &[turn.clone(), turn].concat());
+        let mut replay = Replay::make(&recording, &mut indexer).unwrap();
+        assert_eq!((replay.steps(), replay.turns()), (8, 3));
+
+        replay.seek(6);
+        assert_eq!(replay.turn(), 2);
+        // The draw and the switch
+        replay.previous_turn();
+        assert_eq!((replay.position(), replay.log.len()), (4, 2));
+        replay.previous_turn();
+        assert_eq!(replay.position(), 0);
+        replay.next_turn();
+        assert_eq!(replay.position(), 4);
+        replay.next_turn();
+        replay.next_turn();
+        assert_eq!((replay.position(), replay.turn()), (8, 3));
+
+        replay.jump_to_turn(2);
+        assert_eq!(replay.position(), 4);
+        replay.jump_to_turn(9);
+        assert_eq!(replay.position(), 8);
+        replay.jump_to_turn(0);
+        assert_eq!(replay.position(), 0);
+    }
+
+    #[test]
+    fn seen_recordings_step_through_what_was_seen()
+    {
+        let (mut st, indexer) = game();
+        let mut mode = st.input_mode_of(Player::Player2);
+        let mut recording = SeenRecording::start(Player::Player2);
+        recording.record(&PlayerView::make(&st, Player::Player2, &mode), &[], &indexer);
+        st.update_as_other(&mut mode, &Input::Deck);
+        let events = st.update_as_other(&mut mode, &Input::Deck);
+        let drawn = PlayerView::make(&st, Player::Player2, &mode);
+        recording.record(&drawn, &events, &indexer);
+        assert_eq!(recording.cards.len(), 20);
+
+        let path = std::env::temp_dir().join(format!("seen-{}.json", std::process::id()));
+        write(&recording, &path).unwrap();
+        let read_back = read(&path);
+        fs::remove_file(&path).unwrap();
+        let Ok(Recorded::Seen(recording)) = read_back else {
+            panic!("Didn't read back a seen recording");
+        };
+
+        let mut replay = Replay::seen(&recording, &mut CardIndexer::default()).unwrap();
+        assert_eq!((replay.steps(), replay.log.len()), (1, 0));
+        replay.step_forward();
+        assert_eq!(replay.log.events(), events);
+        assert_eq!(json(replay.view()), json(&drawn));
+
+        // The views' indices only work for an empty indexer
+        let mut used = CardIndexer::default();
+        used.intern("mt", "pachirisu-mt-86");
+        assert!(Replay::seen(&recording, &mut used).is_err());
+    }
+}
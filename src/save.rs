@@ -0,0 +1,286 @@
+//! Saved games, as JSON. Cards are written as `"<set>/<card>"`, the same
+//! set directory and image name a deck list uses (so the path of the image
+//! under the card root, without `.jpg`), never as `Card` indices, which
+//! depend on the order decks were loaded in. A save looks like:
+//!
+//! ```json
+//! {
+//!   "version": 3,
+//!   "currently_viewing": "Player1",
+//!   "rng": { "seed": 1234, "position": "5678" },
+//!   "player1": {
+//!     "slots": [ { "cards": ["ds/flareon-ex-delta-species-ds-108"], "damage": 3 }, ... ],
+//!     "hand": ["mt/pachirisu-mt-86"],
+//!     "discard": [],
+//!     "deck": ["..."],
+//!     "lost_zone": [],
+//!     "prizes": [ { "card": "...", "is_face_up": false } ],
+//!     "stadium": []
+//!   },
+//!   "player2": { ... }
+//! }
+//! ```
+//!
+//! `rng` is the game's seed and how far into it the game has got (as a
+//! string, the number is too big for some JSON readers), so shuffles and
+//! rolls after loading come out the same as if the game hadn't stopped.
+//!
+//! Piles are listed bottom first, so the last card of `deck` is the top.
+//! What was selected isn't saved: a loaded game starts with nothing selected
+//! on `currently_viewing`'s side. Version 2 saves, which had it as
+//! `input_mode`, still load.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::load_cards::CardIndexer;
+use crate::state::{Card, CardLayout, Player, PokemonSlot, PrizeCard, State};
+
+pub const SAVE_VERSION: u32 = 3;
+/// The oldest version `load_game` reads.
+const OLDEST_SAVE_VERSION: u32 = 2;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavedSlot
+{
+    pub cards: Vec<String>,
+    pub damage: u8,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavedPrize
+{
+    pub card: String,
+    pub is_face_up: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavedLayout
+{
+    pub slots: Vec<SavedSlot>,
+    pub hand: Vec<String>,
+    pub discard: Vec<String>,
+    pub deck: Vec<String>,
+    pub lost_zone: Vec<String>,
+    pub prizes: Vec<SavedPrize>,
+    pub stadium: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavedRng
+{
+    pub seed: u64,
+    pub position: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavedGame
+{
+    pub version: u32,
+    pub rng: SavedRng,
+    pub currently_viewing: Player,
+    pub player1: SavedLayout,
+    pub player2: SavedLayout,
+}
+
+fn card_name(card: Card, indexer: &CardIndexer) -> String
+{
+    let (set, card_id) = indexer.identity(card);
+    format!("{}/{}", set, card_id)
+}
+
+pub fn pile_names(pile: &[Card], indexer: &CardIndexer) -> Vec<String>
+{
+    pile.iter().map(|card| card_name(*card, indexer)).collect()
+}
+
+fn card_from_name(name: &str, indexer: &mut CardIndexer) -> Result<Card, String>
+{
+    match name.split_once('/') {
+        Some((set, card)) if !set.is_empty() && !card.is_empty() => Ok(indexer.intern(set, card)),
+        _ => Err(format!("{:?} isn't a card, should look like <set>/<card>", name)),
+    }
+}
+
+pub fn pile_from_names(names: &[String], indexer: &mut CardIndexer) -> Result<Vec<Card>, String>
+{
+    names.iter().map(|name| card_from_name(name, indexer)).collect()
+}
+
+impl SavedLayout
+{
+    pub fn from_layout(layout: &CardLayout, indexer: &CardIndexer) -> Self
+    {
+        SavedLayout {
+            slots: layout
+                .slots
+                .iter()
+                .map(|slot| SavedSlot {
+                    cards: pile_names(&slot.cards, indexer),
+                    damage: slot.damage,
+                })
+                .collect(),
+            hand: pile_names(&layout.hand, indexer),
+            discard: pile_names(&layout.discard, indexer),
+            deck: pile_names(&layout.deck, indexer),
+            lost_zone: pile_names(&layout.lost_zone, indexer),
+            prizes: layout
+                .prizes
+                .iter()
+                .map(|prize| SavedPrize {
+                    card: card_name(prize.card, indexer),
+                    is_face_up: prize.is_face_up,
+                })
+                .collect(),
+            stadium: pile_names(&layout.stadium, indexer),
+        }
+    }
+
+    /// Cards the indexer doesn't know yet are added to it.
+    pub fn to_layout(&self, indexer: &mut CardIndexer) -> Result<CardLayout, String>
+    {
+        let mut slots = Vec::new();
+        for slot in self.slots.iter() {
+            let cards = pile_from_names(&slot.cards, indexer)?;
+            slots.push(PokemonSlot { cards, damage: slot.damage });
+        }
+        let mut prizes = Vec::new();
+        for prize in self.prizes.iter() {
+            let card = card_from_name(&prize.card, indexer)?;
+            prizes.push(PrizeCard { card, is_face_up: prize.is_face_up });
+        }
+        Ok(CardLayout {
+            slots,
+            hand: pile_from_names(&self.hand, indexer)?,
+            discard: pile_from_names(&self.discard, indexer)?,
+            deck: pile_from_names(&self.deck, indexer)?,
+            lost_zone: pile_from_names(&self.lost_zone, indexer)?,
+            prizes,
+            stadium: pile_from_names(&self.stadium, indexer)?,
+        })
+    }
+}
+
+impl SavedGame
+{
+    pub fn from_state(st: &State, indexer: &CardIndexer) -> Self
+    {
+        SavedGame {
+            version: SAVE_VERSION,
+            rng: SavedRng { seed: st.seed(), position: st.rng_position().to_string() },
+            currently_viewing: st.currently_viewing,
+            player1: SavedLayout::from_layout(&st.player1_layout, indexer),
+            player2: SavedLayout::from_layout(&st.player2_layout, indexer),
+        }
+    }
+
+    pub fn to_state(&self, indexer: &mut CardIndexer) -> Result<State, String>
+    {
+        let mut st = State::make(Vec::new(), Vec::new(), self.rng.seed);
+        let position = self
+            .rng
+            .position
+            .parse()
+            .map_err(|_| format!("{:?} isn't an RNG position", self.rng.position))?;
+        st.restore_rng(self.rng.seed, position);
+        st.player1_layout = self.player1.to_layout(indexer)?;
+        st.player2_layout = self.player2.to_layout(indexer)?;
+        st.start_on(self.currently_viewing);
+        Ok(st)
+    }
+}
+
+pub fn save_game(path: &Path, st: &State, indexer: &CardIndexer) -> Result<(), String>
+{
+    let saved = SavedGame::from_state(st, indexer);
+    let json = serde_json::to_string_pretty(&saved).map_err(|e| e.to_string())?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Problem creating {:?}: {}", dir, e))?;
+    }
+    fs::write(path, json).map_err(|e| format!("Problem writing {:?}: {}", path, e))
+}
+
+/// Cards in the save that the indexer doesn't know yet are added to it.
+pub fn load_game(path: &Path, indexer: &mut CardIndexer) -> Result<State, String>
+{
+    let contents =
+        fs::read_to_string(path).map_err(|e| format!("Problem reading {:?}: {}", path, e))?;
+    let saved: SavedGame =
+        serde_json::from_str(&contents).map_err(|e| format!("Problem parsing {:?}: {}", path, e))?;
+    if !(OLDEST_SAVE_VERSION..=SAVE_VERSION).contains(&saved.version) {
+        return Err(format!(
+            "{:?} is a version {} save, this version reads versions {} to {}",
+            path, saved.version, OLDEST_SAVE_VERSION, SAVE_VERSION
+        ));
+    }
+    saved.to_state(indexer)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::game_log::GameEvent;
+    use crate::input::Input;
+    use crate::state::InputMode;
+
+    fn shuffle_and_roll(st: &mut State) -> (Vec<Card>, Vec<GameEvent>)
+    {
+        st.update(&Input::Deck);
+        st.update(&Input::Shuffle);
+        st.update(&Input::Cancel);
+        let rolls = (0..5).flat_map(|_| st.update(&Input::Roll)).collect();
+        (st.player1_layout.deck.clone(), rolls)
+    }
+
+    #[test]
+    fn loading_carries_on_the_same_random_numbers()
+    {
+        let mut indexer = CardIndexer::default();
+        let deck1 = (0..30).map(|i| indexer.intern("s", &format!("a{}", i))).collect();
+        let deck2 = (0..30).map(|i| indexer.intern("s", &format!("b{}", i))).collect();
+        let mut st = State::make(deck1, deck2, 1234);
+        st.setup();
+        st.update(&Input::Roll);
+
+        let json = serde_json::to_string(&SavedGame::from_state(&st, &indexer)).unwrap();
+        let saved: SavedGame = serde_json::from_str(&json).unwrap();
+        let mut loaded = saved.to_state(&mut indexer).unwrap();
+        assert_eq!(loaded.seed(), st.seed());
+        assert_eq!(loaded.rng_position(), st.rng_position());
+        assert_eq!(loaded.player1_layout, st.player1_layout);
+
+        let (deck, rolls) = shuffle_and_roll(&mut st);
+        let (loaded_deck, loaded_rolls) = shuffle_and_roll(&mut loaded);
+        assert_eq!(loaded_deck, deck);
+        assert_eq!(loaded_rolls, rolls);
+        assert_eq!(rolls.len(), 5);
+    }
+
+    #[test]
+    fn loading_starts_with_nothing_selected()
+    {
+        let mut indexer = CardIndexer::default();
+        let mut st = State::make(vec![indexer.intern("s", "a")], Vec::new(), 1);
+        st.start_on(Player::Player2);
+        st.update(&Input::Deck);
+        st.update(&Input::Select);
+
+        let mut json = serde_json::to_value(SavedGame::from_state(&st, &indexer)).unwrap();
+        assert!(json.get("input_mode").is_none());
+        // Version 2 saves had what was selected too
+        json["version"] = 2.into();
+        json["input_mode"] = serde_json::to_value(&st.input_mode).unwrap();
+        let saved: SavedGame = serde_json::from_value(json).unwrap();
+        let loaded = saved.to_state(&mut indexer).unwrap();
+        assert_eq!(loaded.currently_viewing, Player::Player2);
+        match loaded.input_mode {
+            InputMode::Selecting(selecting) => {
+                assert!(selecting.selected.is_empty());
+                assert_eq!(selecting.current_highlight.player(), Player::Player2);
+            },
+            mode => panic!("{mode:?}"),
+        }
+    }
+}
@@ -0,0 +1,393 @@
+//! Watching a game from another window. A game started with
+//! `--spectate-port` listens on localhost, and `tcgclient watch` connects and
+//! is sent a `PlayerView` every time the board changes. Watchers hold no
+//! `State` and send nothing after saying hello, so they can't play.
+//!
+//! The watcher picks what they see:
+//!
+//! - `Neutral`: only what's public, from the side being played.
+//! - `Player`: what that player sees.
+//! - `Coach`: both hands and every prize.
+//!
+//! and how long each view is held back first, so a coach's view can run
+//! behind the game. The game sets how far behind any view showing hidden
+//! cards has to be: the coach's view, either player's view in a hot-seat
+//! game, or the joining player's view in a hosted one. Watchers asking for
+//! less are turned away. Messages are JSON lines as in `net`:
+//!
+//! ```json
+//! {"Hello":{"version":1,"view":"Coach","delay_secs":30}}
+//! ```
+//!
+//! from the watcher, and from the game:
+//!
+//! ```json
+//! {"Welcome":{"version":1}}
+//! {"Cards":["DS/flareon-ex-delta-species-ds-108","MT/pachirisu-mt-86"]}
+//! {"View":{"viewer":"Player1","player1":{...},"player2":{...},...}}
+//! ```
+//!
+//! Events aren't sent, since a card moving from deck to hand names the card.
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::net::{TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+
+use crate::load_cards::CardIndexer;
+use crate::net::{self, Connection};
+use crate::state::{InputMode, Player, State};
+use crate::view::PlayerView;
+
+pub const SPECTATE_VERSION: u32 = 1;
+pub const DEFAULT_SPECTATE_PORT: u16 = 7778;
+/// How far behind views showing hidden cards run when hosting, unless the
+/// host says otherwise, so the host can't watch the other player's hand.
+pub const DEFAULT_HOST_SPECTATE_DELAY: Duration = Duration::from_secs(60);
+/// How long a new watcher has to say hello.
+const HELLO_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SpectatorView
+{
+    Neutral,
+    Player(Player),
+    Coach,
+}
+
+impl fmt::Display for SpectatorView
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self {
+            SpectatorView::Neutral => write!(f, "neutral view"),
+            SpectatorView::Player(player) => write!(f, "{}'s view", player),
+            SpectatorView::Coach => write!(f, "coach's view"),
+        }
+    }
+}
+
+impl SpectatorView
+{
+    /// `input_mode_of` gives each player's mode, for their view.
+    pub fn project(&self, st: &State, input_mode_of: &dyn Fn(Player) -> InputMode) -> PlayerView
+    {
+        match self {
+            SpectatorView::Neutral => PlayerView::neutral(st),
+            SpectatorView::Player(player) => {
+                PlayerView::make(st, *player, &input_mode_of(*player))
+            },
+            SpectatorView::Coach => PlayerView::all_info(st),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum WatcherMessage
+{
+    Hello
+    {
+        version: u32, view: SpectatorView, delay_secs: u64
+    },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum BroadcastMessage
+{
+    Welcome
+    {
+        version: u32
+    },
+    /// The connection is about to close, and why.
+    Refused(String),
+    /// As in `net::HostMessage::Cards`.
+    Cards(Vec<String>),
+    View(Box<PlayerView>),
+}
+
+struct Watcher
+{
+    connection: Connection<WatcherMessage>,
+    view: SpectatorView,
+    delay: Duration,
+    cards_sent: usize,
+    // To tell whether the board has changed since the last view
+    last_json: String,
+    // Views waiting out the delay, oldest first
+    queued: VecDeque<(Instant, PlayerView)>,
+}
+
+/// The game's side: everyone watching it.
+pub struct Spectators
+{
+    listener: TcpListener,
+    /// Connections that haven't said hello yet, and when they connected.
+    pending: Vec<(Connection<WatcherMessage>, Instant)>,
+    watchers: Vec<Watcher>,
+    /// The least delay for views showing cards hidden from a player.
+    min_delay: Duration,
+    /// The side played from this machine when hosting, whose view can be
+    /// watched without a delay.
+    host: Option<Player>,
+}
+
+impl Spectators
+{
+    /// Listens on `port`, for connections from this machine only. `host` is
+    /// the hosting player's side in a network game, None in a hot-seat one.
+    pub fn new(port: u16, min_delay: Duration, host: Option<Player>) -> Result<Self, String>
+    {
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .map_err(|e| format!("Couldn't listen for spectators on port {}: {}", port, e))?;
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+        Ok(Spectators { listener, pending: Vec::new(), watchers: Vec::new(), min_delay, host })
+    }
+
+    pub fn port(&self) -> u16
+    {
+        self.listener.local_addr().map(|address| address.port()).unwrap_or(0)
+    }
+
+    /// Lets watchers connect, queues a view for each one the board has
+    /// changed for, and sends the ones whose delay is up. Call often.
+    pub fn poll(
+        &mut self,
+        st: &State,
+        input_mode_of: &dyn Fn(Player) -> InputMode,
+        indexer: &CardIndexer,
+    )
+    {
+        self.accept();
+
+        let now = Instant::now();
+        self.watchers.retain_mut(|watcher| {
+            if let Err(e) = watcher.connection.receive() {
+                println!("A spectator left: {}", e);
+                return false;
+            }
+            let view = watcher.view.project(st, input_mode_of);
+            let json = serde_json::to_string(&view).unwrap_or_default();
+            if json != watcher.last_json {
+                watcher.last_json = json;
+                watcher.queued.push_back((now + watcher.delay, view));
+            }
+            match watcher.send_due(now, indexer) {
+                Ok(()) => true,
+                Err(e) => {
+                    println!("A spectator left: {}", e);
+                    false
+                },
+            }
+        });
+    }
+
+    fn accept(&mut self)
+    {
+        match self.listener.accept() {
+            Ok((stream, _)) => match Connection::<WatcherMessage>::new(stream) {
+                Ok(connection) => self.pending.push((connection, Instant::now())),
+                Err(e) => eprintln!("Problem accepting a spectator: {}", e),
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => (),
+            Err(e) => eprintln!("Problem accepting a spectator: {}", e),
+        }
+
+        for (mut connection, since) in std::mem::take(&mut self.pending) {
+            let hello = match connection.receive() {
+                Ok(messages) => messages.into_iter().next(),
+                Err(e) => {
+                    eprintln!("Problem accepting a spectator: {}", e);
+                    continue;
+                },
+            };
+            match hello {
+                Some(WatcherMessage::Hello { version, view, delay_secs })
+                    if version == SPECTATE_VERSION =>
+                {
+                    self.admit(connection, view, delay_secs)
+                },
+                Some(WatcherMessage::Hello { version, .. }) => {
+                    let reason = format!(
+                        "The game speaks version {}, not version {}",
+                        SPECTATE_VERSION, version
+                    );
+                    eprintln!("Turned away a spectator: {}", reason);
+                    let _ = connection.send(&BroadcastMessage::Refused(reason));
+                },
+                None if since.elapsed() >= HELLO_TIMEOUT => {
+                    eprintln!("Problem accepting a spectator: no hello")
+                },
+                None => self.pending.push((connection, since)),
+            }
+        }
+    }
+
+    /// Why a watcher can't have `view` this far behind, if they can't.
+    fn refusal(&self, view: SpectatorView, delay: Duration) -> Option<String>
+    {
+        let shows_hidden = match view {
+            SpectatorView::Neutral => false,
+            SpectatorView::Player(player) => self.host != Some(player),
+            SpectatorView::Coach => true,
+        };
+        let secs = self.min_delay.as_secs();
+        (shows_hidden && delay < self.min_delay)
+            .then(|| format!("Watching {} needs a delay of at least {}s", view, secs))
+    }
+
+    fn admit(
+        &mut self,
+        mut connection: Connection<WatcherMessage>,
+        view: SpectatorView,
+        delay_secs: u64,
+    )
+    {
+        if let Some(reason) = self.refusal(view, Duration::from_secs(delay_secs)) {
+            eprintln!("Turned away a spectator: {}", reason);
+            let _ = connection.send(&BroadcastMessage::Refused(reason));
+            return;
+        }
+        if connection.send(&BroadcastMessage::Welcome { version: SPECTATE_VERSION }).is_err() {
+            return;
+        }
+        println!("A spectator is watching ({}, {}s behind)", view, delay_secs);
+        self.watchers.push(Watcher {
+            connection,
+            view,
+            delay: Duration::from_secs(delay_secs),
+            cards_sent: 0,
+            last_json: String::new(),
+            queued: VecDeque::new(),
+        });
+    }
+}
+
+impl Watcher
+{
+    fn send_due(&mut self, now: Instant, indexer: &CardIndexer) -> Result<(), String>
+    {
+        while self.queued.front().is_some_and(|(due, _)| *due <= now) {
+            let (_, view) = self.queued.pop_front().unwrap();
+            // Every card known so far, which covers the ones in the view
+            if self.cards_sent < indexer.len() {
+                let names = net::card_names(indexer, self.cards_sent);
+                self.connection.send(&BroadcastMessage::Cards(names))?;
+                self.cards_sent = indexer.len();
+            }
+            self.connection.send(&BroadcastMessage::View(Box::new(view)))?;
+        }
+        Ok(())
+    }
+}
+
+/// The watching side.
+pub struct Spectator
+{
+    game: Connection<BroadcastMessage>,
+    view: Option<PlayerView>,
+}
+
+impl Spectator
+{
+    pub fn watch(address: &str, view: SpectatorView, delay: Duration) -> Result<Self, String>
+    {
+        let stream = TcpStream::connect(address)
+            .map_err(|e| format!("Couldn't connect to {}: {}", address, e))?;
+        let mut game = Connection::<BroadcastMessage>::new(stream)?;
+        let delay_secs = delay.as_secs();
+        game.send(&WatcherMessage::Hello { version: SPECTATE_VERSION, view, delay_secs })?;
+        match game.receive_within(HELLO_TIMEOUT)? {
+            BroadcastMessage::Welcome { .. } => (),
+            BroadcastMessage::Refused(reason) => {
+                return Err(format!("The game said no: {}", reason))
+            },
+            message => return Err(format!("Expected a welcome, got {:?}", message)),
+        }
+        Ok(Spectator { game, view: None })
+    }
+
+    /// The last view sent, if one has been yet.
+    pub fn view(&self) -> Option<&PlayerView>
+    {
+        self.view.as_ref()
+    }
+
+    /// Catches up with the game. New cards are added to the indexer and
+    /// returned, so their metadata and images can be loaded.
+    pub fn poll(&mut self, indexer: &mut CardIndexer) -> Result<Vec<usize>, String>
+    {
+        let mut new_cards = Vec::new();
+        for message in self.game.receive()? {
+            match message {
+                BroadcastMessage::Cards(names) => {
+                    new_cards.extend(net::intern_cards(indexer, &names)?)
+                },
+                BroadcastMessage::View(view) => self.view = Some(*view),
+                BroadcastMessage::Refused(reason) => return Err(reason),
+                BroadcastMessage::Welcome { .. } => (),
+            }
+        }
+        Ok(new_cards)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn views_showing_hidden_cards_need_the_games_delay()
+    {
+        let hosting = Spectators::new(0, MINUTE, Some(Player::Player1)).unwrap();
+        assert_eq!(hosting.refusal(SpectatorView::Neutral, Duration::ZERO), None);
+        assert_eq!(hosting.refusal(SpectatorView::Player(Player::Player1), Duration::ZERO), None);
+        assert!(hosting.refusal(SpectatorView::Player(Player::Player2), Duration::ZERO).is_some());
+        assert_eq!(hosting.refusal(SpectatorView::Player(Player::Player2), MINUTE), None);
+        assert!(hosting.refusal(SpectatorView::Coach, MINUTE / 2).is_some());
+        assert_eq!(hosting.refusal(SpectatorView::Coach, MINUTE), None);
+
+        let hot_seat = Spectators::new(0, MINUTE, None).unwrap();
+        assert!(hot_seat.refusal(SpectatorView::Player(Player::Player1), Duration::ZERO).is_some());
+    }
+
+    #[test]
+    fn views_are_sent_once_their_delay_is_up()
+    {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let mut watching = Connection::<BroadcastMessage>::new(stream).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let mut watcher = Watcher {
+            connection: Connection::new(stream).unwrap(),
+            view: SpectatorView::Neutral,
+            delay: Duration::from_secs(2),
+            cards_sent: 0,
+            last_json: String::new(),
+            queued: VecDeque::new(),
+        };
+        let mut indexer = CardIndexer::default();
+        indexer.intern("ds", "flareon-ex-delta-species-ds-108");
+        // Told apart by the size of player 1's deck
+        let view = |deck_size| PlayerView::neutral(&State::make(vec![0; deck_size], vec![], 0));
+        let start = Instant::now();
+        watcher.queued.push_back((start + Duration::from_secs(1), view(1)));
+        watcher.queued.push_back((start + Duration::from_secs(3), view(2)));
+
+        watcher.send_due(start, &indexer).unwrap();
+        watcher.send_due(start + Duration::from_secs(2), &indexer).unwrap();
+        assert_eq!(watcher.queued.len(), 1);
+        watcher.send_due(start + Duration::from_secs(3), &indexer).unwrap();
+        assert!(watcher.queued.is_empty());
+
+        let mut next = || watching.receive_within(Duration::from_secs(1));
+        assert!(matches!(next(), Ok(BroadcastMessage::Cards(names)) if names.len() == 1));
+        assert!(matches!(next(), Ok(BroadcastMessage::View(view)) if view.player1.deck_size == 1));
+        // The card was only sent the once
+        assert!(matches!(next(), Ok(BroadcastMessage::View(view)) if view.player1.deck_size == 2));
+        assert!(watching.receive_within(Duration::from_millis(100)).is_err());
+    }
+}
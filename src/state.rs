@@ -1,15 +1,31 @@
 use std::collections::HashSet;
+use std::fmt;
 use std::hash::Hash;
 
+use serde::{Deserialize, Serialize};
+
+use crate::game_log::{GameEvent, Zone};
 use crate::input::Input;
 use rand::seq::SliceRandom;
-use rand::{thread_rng, Rng};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 
 pub type Card = usize;
 
 pub type Pile = Vec<Card>;
 
-#[derive(Clone, Debug, Default)]
+/// Announcements sent with a single key.
+pub const EMOTES: [&str; 6] = [
+    "Good luck, have fun!",
+    "Using an Ability",
+    "Playing a Supporter",
+    "Attacking",
+    "Your turn",
+    "Good game!",
+];
+pub const MAX_CHAT_LENGTH: usize = 200;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct PokemonSlot
 {
     pub cards: Pile,
@@ -18,14 +34,14 @@ pub struct PokemonSlot
 
 pub type Slots<T> = Vec<T>;
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct PrizeCard
 {
     pub card: Card,
     pub is_face_up: bool,
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct CardLayout
 {
     pub slots: Slots<PokemonSlot>,
@@ -41,51 +57,60 @@ pub struct CardLayout
     pub stadium: Pile,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+/// A card (or empty Pokémon slot) on either player's side of the board.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum Selection
 {
     Slot
     {
-        slot_index: usize, pokemon_index: Option<usize>
+        player: Player, slot_index: usize, pokemon_index: Option<usize>
     },
     Hand
     {
-        index: usize
+        player: Player, index: usize
     },
     Discard
     {
-        index: usize
+        player: Player, index: usize
     },
     LostZone
     {
-        index: usize
+        player: Player, index: usize
     },
     Prize
     {
-        index: usize
+        player: Player, index: usize
     },
     Stadium
     {
-        index: usize
+        player: Player, index: usize
     },
 }
 
-impl Default for Selection
-{
-    fn default() -> Self { Selection::Slot { slot_index: 0, pokemon_index: None } }
-}
-
 impl Selection
 {
+    /// Whose side of the board it's on.
+    pub fn player(&self) -> Player
+    {
+        match self {
+            Selection::Slot { player, .. }
+            | Selection::Hand { player, .. }
+            | Selection::Discard { player, .. }
+            | Selection::LostZone { player, .. }
+            | Selection::Prize { player, .. }
+            | Selection::Stadium { player, .. } => *player,
+        }
+    }
+
     fn deepest_index(&self) -> Option<usize>
     {
         match self {
             Selection::Slot { pokemon_index, .. } => pokemon_index.clone(),
-            Selection::Hand { index }
-            | Selection::Discard { index }
-            | Selection::LostZone { index }
-            | Selection::Prize { index }
-            | Selection::Stadium { index } => Some(*index),
+            Selection::Hand { index, .. }
+            | Selection::Discard { index, .. }
+            | Selection::LostZone { index, .. }
+            | Selection::Prize { index, .. }
+            | Selection::Stadium { index, .. } => Some(*index),
         }
     }
 
@@ -98,21 +123,27 @@ impl Selection
                 ((*u as i32) + change) as usize
             }
         };
+        let player = self.player();
         match self {
-            Selection::Slot { pokemon_index: Some(u), slot_index } => {
-                Selection::Slot { slot_index: *slot_index, pokemon_index: Some(apply(u)) }
+            Selection::Slot { pokemon_index: Some(u), slot_index, .. } => Selection::Slot {
+                player,
+                slot_index: *slot_index,
+                pokemon_index: Some(apply(u)),
             },
-            Selection::Hand { index } => Selection::Hand { index: apply(index) },
-            Selection::Discard { index } => Selection::Discard { index: apply(index) },
-            Selection::LostZone { index } => Selection::LostZone { index: apply(index) },
-            Selection::Prize { index } => Selection::Prize { index: apply(index) },
-            Selection::Stadium { index } => Selection::Stadium { index: apply(index) },
+            Selection::Hand { index, .. } => Selection::Hand { player, index: apply(index) },
+            Selection::Discard { index, .. } => Selection::Discard { player, index: apply(index) },
+            Selection::LostZone { index, .. } => {
+                Selection::LostZone { player, index: apply(index) }
+            },
+            Selection::Prize { index, .. } => Selection::Prize { player, index: apply(index) },
+            Selection::Stadium { index, .. } => Selection::Stadium { player, index: apply(index) },
             _ => self.clone(),
         }
     }
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(bound(deserialize = "T: Deserialize<'de> + Eq + Hash"))]
 pub struct SelectingState<T: Clone>
 {
     pub selected: HashSet<T>,
@@ -147,15 +178,19 @@ pub type LayoutSelectingState = SelectingState<Selection>;
 
 pub type PileSelectingState = SelectingState<usize>;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum PreviousMovingState
 {
     Selecting(LayoutSelectingState),
     DeckSearch(PileSelectingState),
-    Look(PileSelectingState),
+    Look
+    {
+        count: usize,
+        st: PileSelectingState,
+    },
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum MoveAwaitedInput
 {
     Any,
@@ -165,32 +200,72 @@ pub enum MoveAwaitedInput
     },
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum InputMode
 {
     Selecting(LayoutSelectingState),
     Deck,
     DeckSearch(PileSelectingState),
-    Look(PileSelectingState),
+    /// Looking at the top `count` cards of the deck. Indices count down from
+    /// the top, so 0 is the top card.
+    Look
+    {
+        count: usize,
+        st: PileSelectingState,
+    },
+    /// Looking through the whole deck without searching it, so nothing can
+    /// be selected or moved.
+    Observe,
     Move
     {
         awaited_input: MoveAwaitedInput,
+        /// Whose side of the board the cards are going to.
+        destination: Player,
         previous_state: PreviousMovingState,
     },
     Swap
     {
+        player: Player,
         first_slot: Option<usize>,
     },
+    /// Typing a chat message, to go back to `previous` once it's sent.
+    Chat
+    {
+        typed: String,
+        previous: Box<InputMode>,
+    },
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum Player
 {
     Player1,
     Player2,
 }
 
-#[derive(Clone, Debug)]
+impl Player
+{
+    pub fn other(self) -> Player
+    {
+        match self {
+            Player::Player1 => Player::Player2,
+            Player::Player2 => Player::Player1,
+        }
+    }
+}
+
+impl fmt::Display for Player
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self {
+            Player::Player1 => write!(f, "Player 1"),
+            Player::Player2 => write!(f, "Player 2"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum UIAlert
 {
     Shuffled,
@@ -205,6 +280,10 @@ pub struct State
     pub currently_viewing: Player,
     pub input_mode: InputMode,
     pub ui_alert: Option<UIAlert>,
+    // Everything random goes through this, so a seed replays a whole game.
+    // Undo puts it back too, so redoing a shuffle can't give a new order
+    seed: u64,
+    rng: ChaCha8Rng,
     // To disallow making States without the make function
     #[allow(dead_code)]
     made: (),
@@ -212,10 +291,10 @@ pub struct State
 
 impl State
 {
-    pub fn make(deck1: Pile, deck2: Pile) -> Self
+    /// Decks aren't checked here, see `validation`. The same seed and inputs
+    /// always give the same game.
+    pub fn make(deck1: Pile, deck2: Pile, seed: u64) -> Self
     {
-        assert_eq!(deck1.len(), 60);
-        assert_eq!(deck2.len(), 60);
         let empty_slots: Slots<PokemonSlot> = vec![PokemonSlot { cards: vec![], damage: 0 }; 6];
         State {
             player1_layout: CardLayout {
@@ -225,8 +304,17 @@ impl State
             },
             player2_layout: CardLayout { deck: deck2, slots: empty_slots, ..Default::default() },
             currently_viewing: Player::Player1,
-            input_mode: InputMode::Selecting(Default::default()),
+            input_mode: InputMode::Selecting(SelectingState {
+                selected: HashSet::new(),
+                current_highlight: Selection::Slot {
+                    player: Player::Player1,
+                    slot_index: 0,
+                    pokemon_index: None,
+                },
+            }),
             ui_alert: None,
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
             made: (),
         }
     }
@@ -234,9 +322,9 @@ impl State
     /// Shuffle decks and put up 6 prizes.
     pub fn setup(&mut self)
     {
-        let setup_layout = |layout: &mut CardLayout| {
-            let mut rng = thread_rng();
-            layout.deck.shuffle(&mut rng);
+        let rng = &mut self.rng;
+        let mut setup_layout = |layout: &mut CardLayout| {
+            layout.deck.shuffle(rng);
             for _ in 0..6 {
                 if let Some(top_card) = layout.deck.pop() {
                     layout.prizes.push(PrizeCard { card: top_card, is_face_up: false });
@@ -247,25 +335,90 @@ impl State
         setup_layout(&mut self.player2_layout);
     }
 
-    fn current_layout(&self) -> &CardLayout
+    pub fn seed(&self) -> u64
+    {
+        self.seed
+    }
+
+    /// How many random words have been used since seeding, for saving.
+    pub fn rng_position(&self) -> u128
     {
-        match self.currently_viewing {
+        self.rng.get_word_pos()
+    }
+
+    /// Carries on a saved game's random numbers where they left off.
+    pub fn restore_rng(&mut self, seed: u64, position: u128)
+    {
+        self.seed = seed;
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+        self.rng.set_word_pos(position);
+    }
+
+    /// Starts on `player`'s side, with nothing selected.
+    pub fn start_on(&mut self, player: Player)
+    {
+        self.currently_viewing = player;
+        self.input_mode = InputMode::Selecting(self.selecting());
+    }
+
+    /// The mode `player` is in: the current one if it's their side being
+    /// viewed, otherwise nothing selected.
+    pub fn input_mode_of(&self, player: Player) -> InputMode
+    {
+        if player == self.currently_viewing {
+            self.input_mode.clone()
+        } else {
+            let current_highlight = Selection::Slot { player, slot_index: 0, pokemon_index: None };
+            InputMode::Selecting(SelectingState { selected: HashSet::new(), current_highlight })
+        }
+    }
+
+    pub fn layout(&self, player: Player) -> &CardLayout
+    {
+        match player {
             Player::Player1 => &self.player1_layout,
             Player::Player2 => &self.player2_layout,
         }
     }
 
-    fn current_layout_mut(&mut self) -> &mut CardLayout
+    fn layout_mut(&mut self, player: Player) -> &mut CardLayout
     {
-        match self.currently_viewing {
+        match player {
             Player::Player1 => &mut self.player1_layout,
             Player::Player2 => &mut self.player2_layout,
         }
     }
 
+    pub fn current_layout(&self) -> &CardLayout
+    {
+        self.layout(self.currently_viewing)
+    }
+
+    fn current_layout_mut(&mut self) -> &mut CardLayout
+    {
+        self.layout_mut(self.currently_viewing)
+    }
+
+    /// Nothing selected and the Active spot highlighted, on the side being
+    /// viewed.
+    fn selecting(&self) -> LayoutSelectingState
+    {
+        let current_highlight =
+            Selection::Slot { player: self.currently_viewing, slot_index: 0, pokemon_index: None };
+        SelectingState { selected: HashSet::new(), current_highlight }
+    }
+
+    /// The first card in a slot, or the empty slot.
+    fn slot_selection(&self, player: Player, slot_index: usize) -> Selection
+    {
+        let cards = &self.layout(player).slots[slot_index].cards;
+        let pokemon_index = if cards.is_empty() { None } else { Some(0) };
+        Selection::Slot { player, slot_index, pokemon_index }
+    }
+
     fn highlighted_list_length(&self, h: &Selection) -> Option<usize>
     {
-        let layout = self.current_layout();
+        let layout = self.layout(h.player());
         match h {
             Selection::Slot { slot_index, .. } => Some(layout.slots[*slot_index].cards.len()),
             Selection::Hand { .. } => Some(layout.hand.len()),
@@ -276,16 +429,23 @@ impl State
         }
     }
 
+    /// None for cards that are face down, or in the other player's hand.
     pub fn card_at(&self, selection: &Selection) -> Option<Card>
     {
-        let layout = self.current_layout();
+        let layout = self.layout(selection.player());
         match selection {
-            Selection::Hand { index } => Some(layout.hand[*index]),
-            Selection::Slot { slot_index, pokemon_index } => {
+            Selection::Hand { player, index } => {
+                if *player == self.currently_viewing {
+                    Some(layout.hand[*index])
+                } else {
+                    None
+                }
+            },
+            Selection::Slot { slot_index, pokemon_index, .. } => {
                 pokemon_index.map(|pi| layout.slots[*slot_index].cards[pi])
             },
-            Selection::Discard { index } => Some(layout.discard[*index]),
-            Selection::Prize { index } => {
+            Selection::Discard { index, .. } => Some(layout.discard[*index]),
+            Selection::Prize { index, .. } => {
                 let prize = &layout.prizes[*index];
                 if prize.is_face_up {
                     Some(prize.card)
@@ -293,8 +453,8 @@ impl State
                     None
                 }
             },
-            Selection::LostZone { index } => Some(layout.lost_zone[*index]),
-            Selection::Stadium { index } => Some(layout.stadium[*index]),
+            Selection::LostZone { index, .. } => Some(layout.lost_zone[*index]),
+            Selection::Stadium { index, .. } => Some(layout.stadium[*index]),
         }
     }
 
@@ -304,16 +464,25 @@ impl State
         layout.deck.get(selection).map(|u| *u)
     }
 
-    pub fn update(&mut self, input: &Input)
+    /// Returns what changed, for the `GameLog`.
+    pub fn update(&mut self, input: &Input) -> Vec<GameEvent>
     {
         self.ui_alert = None;
+        if let Some(events) = self.update_chat(input) {
+            return events;
+        }
 
         use Input as I;
 
+        let player = self.currently_viewing;
+        let mut events = Vec::new();
+
         let leave_unchanged = self.input_mode.clone();
 
-        let pile_change = |f: &dyn Fn(&CardLayout) -> usize, sel, st: &LayoutSelectingState| {
-            if f(self.current_layout()) > 0 {
+        let pile_change = |f: &dyn Fn(&CardLayout) -> usize,
+                           sel: Selection,
+                           st: &LayoutSelectingState| {
+            if f(self.layout(sel.player())) > 0 {
                 let st2 = st.change_highlight(sel);
                 InputMode::Selecting(st2)
             } else {
@@ -323,12 +492,13 @@ impl State
         };
 
         let next_input_mode = match &self.input_mode {
-            InputMode::Selecting(st) => match input {
-                I::Left => {
+            // Zones are picked on whichever side of the board the highlight is
+            InputMode::Selecting(st) => match (input, st.current_highlight.player()) {
+                (I::Left, _) => {
                     let h = st.current_highlight.change_deepest_index(-1);
                     InputMode::Selecting(st.change_highlight(h))
                 },
-                I::Right => {
+                (I::Right, _) => {
                     let l = self.highlighted_list_length(&st.current_highlight);
                     let i = st.current_highlight.deepest_index();
                     match (l, i) {
@@ -344,56 +514,67 @@ impl State
                     }
                 },
 
-                I::Slot(u) => {
-                    let slots = &self.current_layout().slots;
-                    if *u > slots.len() || *u == 0 {
+                (I::Slot(u), side) => {
+                    if *u > self.layout(side).slots.len() || *u == 0 {
                         leave_unchanged
                     } else {
-                        let adjusted_u = u - 1;
-                        let pokemon_index =
-                            if slots[adjusted_u].cards.len() == 0 { None } else { Some(0) };
-                        let h = Selection::Slot { slot_index: adjusted_u, pokemon_index };
+                        let h = self.slot_selection(side, u - 1);
                         InputMode::Selecting(st.change_highlight(h))
                     }
                 },
 
-                I::Hand => pile_change(&|l| l.hand.len(), Selection::Hand { index: 0 }, st),
-                I::Discard => {
-                    pile_change(&|l| l.discard.len(), Selection::Discard { index: 0 }, st)
+                (I::Hand, side) => {
+                    pile_change(&|l| l.hand.len(), Selection::Hand { player: side, index: 0 }, st)
                 },
-                I::Prizes => pile_change(&|l| l.prizes.len(), Selection::Prize { index: 0 }, st),
-                I::LostZone => {
-                    pile_change(&|l| l.lost_zone.len(), Selection::LostZone { index: 0 }, st)
-                },
-                I::Stadium => {
-                    pile_change(&|l| l.stadium.len(), Selection::Stadium { index: 0 }, st)
+                (I::Discard, side) => pile_change(
+                    &|l| l.discard.len(),
+                    Selection::Discard { player: side, index: 0 },
+                    st,
+                ),
+                (I::Prizes, side) => pile_change(
+                    &|l| l.prizes.len(),
+                    Selection::Prize { player: side, index: 0 },
+                    st,
+                ),
+                (I::LostZone, side) => pile_change(
+                    &|l| l.lost_zone.len(),
+                    Selection::LostZone { player: side, index: 0 },
+                    st,
+                ),
+                (I::Stadium, side) => pile_change(
+                    &|l| l.stadium.len(),
+                    Selection::Stadium { player: side, index: 0 },
+                    st,
+                ),
+
+                (I::OtherBoard, side) => {
+                    let h = self.slot_selection(side.other(), 0);
+                    InputMode::Selecting(st.change_highlight(h))
                 },
 
-                I::Flip => {
-                    let mut indices_to_flip = Vec::new();
+                (I::Flip, _) => {
+                    let mut prizes_to_flip = Vec::new();
                     for selection in st.selected.iter() {
-                        match *selection {
-                            Selection::Prize { index } => {
-                                indices_to_flip.push(index);
-                            },
-                            _ => (),
+                        if let Selection::Prize { player, index } = *selection {
+                            prizes_to_flip.push((player, index));
                         }
                     }
-                    let layout = self.current_layout_mut();
-                    for index in indices_to_flip {
-                        layout.prizes[index].is_face_up = !layout.prizes[index].is_face_up;
+                    for (player, index) in prizes_to_flip {
+                        let prize = &mut self.layout_mut(player).prizes[index];
+                        let face_up = !prize.is_face_up;
+                        prize.is_face_up = face_up;
+                        events.push(GameEvent::PrizeFlipped { player, index, face_up });
                     }
                     leave_unchanged
                 },
 
-                I::Increment | I::Decrement => {
+                (I::Increment | I::Decrement, _) => {
                     let mut slots_to_affect = Vec::new();
                     for selection in st.selected.iter() {
-                        match *selection {
-                            Selection::Slot { slot_index, pokemon_index: Some(_) } => {
-                                slots_to_affect.push(slot_index);
-                            },
-                            _ => (),
+                        if let Selection::Slot { player, slot_index, pokemon_index: Some(_) } =
+                            *selection
+                        {
+                            slots_to_affect.push((player, slot_index));
                         }
                     }
 
@@ -403,62 +584,79 @@ impl State
                         _ => unreachable!(),
                     };
 
-                    let layout = self.current_layout_mut();
-                    for slot in slots_to_affect {
-                        layout.slots[slot].damage = f(layout.slots[slot].damage);
+                    for (player, slot) in slots_to_affect {
+                        let layout = self.layout_mut(player);
+                        let from = layout.slots[slot].damage;
+                        let to = f(from);
+                        layout.slots[slot].damage = to;
+                        if from != to {
+                            events.push(GameEvent::DamageChanged { player, slot, from, to });
+                        }
                     }
                     leave_unchanged
                 },
 
-                I::Select => InputMode::Selecting(st.add_to_selection()),
+                (I::Select, _) => InputMode::Selecting(st.add_to_selection()),
 
-                I::Cancel => InputMode::Selecting(st.clear_selected()),
+                (I::Cancel, _) => InputMode::Selecting(st.clear_selected()),
 
-                I::Move => InputMode::Move {
+                (I::Move, side) => InputMode::Move {
                     awaited_input: MoveAwaitedInput::Any,
+                    destination: side,
                     previous_state: PreviousMovingState::Selecting(st.clone()),
                 },
 
-                I::Swap => InputMode::Swap { 
+                (I::Swap, side) => InputMode::Swap { 
+                    player: side,
                     first_slot: None, 
                 },
 
-                I::Deck => InputMode::Deck,
+                (I::Deck, _) => InputMode::Deck,
 
-                I::SwitchSides => {
-                    self.currently_viewing = match &self.currently_viewing {
-                        Player::Player1 => Player::Player2,
-                        Player::Player2 => Player::Player1,
-                    };
-                    InputMode::Selecting(LayoutSelectingState::default())
+                (I::SwitchSides, _) => {
+                    self.currently_viewing = self.currently_viewing.other();
+                    events.push(GameEvent::SidesSwitched { to: self.currently_viewing });
+                    InputMode::Selecting(self.selecting())
                 },
 
-                I::Roll => {
-                    let mut rng = thread_rng();
-                    let die_roll = rng.gen_range(1..=6);
+                (I::Roll, _) => {
+                    let die_roll = self.rng.gen_range(1..=6);
                     self.ui_alert = Some(UIAlert::Roll(die_roll));
+                    events.push(GameEvent::DieRolled { player, value: die_roll });
                     leave_unchanged
                 },
 
                 _ => leave_unchanged,
             },
             InputMode::Deck => match input {
-                I::Cancel => {
-                    let selecting_state = Default::default();
-                    InputMode::Selecting(selecting_state)
-                },
+                I::Cancel => InputMode::Selecting(self.selecting()),
 
                 I::Deck => {
                     // DRAW CARD!!!
                     let layout = self.current_layout_mut();
                     if let Some(top_card) = layout.deck.pop() {
                         layout.hand.push(top_card);
+                        events.push(GameEvent::CardMoved {
+                            player,
+                            card: Some(top_card),
+                            from: Zone::Deck,
+                            from_index: layout.deck.len(),
+                            to_player: player,
+                            to: Zone::Hand,
+                            to_index: layout.hand.len() - 1,
+                        });
                     }
                     InputMode::Deck
                 },
 
-                I::Observe => {
-                    todo!()
+                I::Observe => InputMode::Observe,
+
+                I::Look => {
+                    if self.current_layout().deck.is_empty() {
+                        leave_unchanged
+                    } else {
+                        InputMode::Look { count: 1, st: Default::default() }
+                    }
                 },
 
                 I::Select => {
@@ -468,51 +666,49 @@ impl State
                 },
 
                 I::Shuffle => {
-                    let layout = self.current_layout_mut();
-                    let mut rng = thread_rng();
-                    layout.deck.shuffle(&mut rng);
+                    let deck = match self.currently_viewing {
+                        Player::Player1 => &mut self.player1_layout.deck,
+                        Player::Player2 => &mut self.player2_layout.deck,
+                    };
+                    deck.shuffle(&mut self.rng);
                     self.ui_alert = Some(UIAlert::Shuffled);
+                    events.push(GameEvent::DeckShuffled { player });
                     leave_unchanged
                 },
 
                 I::Slot(u) => {
-                    //let st: SelectingState<Selection> = Default::default();
-                    let slots = &self.current_layout().slots;
-                    if *u > slots.len() || *u == 0 {
+                    if *u > self.current_layout().slots.len() || *u == 0 {
                         leave_unchanged
                     } else {
-                        let adjusted_u = u - 1;
-                        let pokemon_index =
-                            if slots[adjusted_u].cards.len() == 0 { None } else { Some(0) };
-                        let h = Selection::Slot { slot_index: adjusted_u, pokemon_index };
-                        InputMode::Selecting(LayoutSelectingState::default().change_highlight(h))
+                        let h = self.slot_selection(player, u - 1);
+                        InputMode::Selecting(self.selecting().change_highlight(h))
                     }
                 },
 
                 I::Hand => pile_change(
                     &|l| l.hand.len(),
-                    Selection::Hand { index: 0 },
-                    &LayoutSelectingState::default(),
+                    Selection::Hand { player, index: 0 },
+                    &self.selecting(),
                 ),
                 I::Discard => pile_change(
                     &|l| l.discard.len(),
-                    Selection::Discard { index: 0 },
-                    &LayoutSelectingState::default(),
+                    Selection::Discard { player, index: 0 },
+                    &self.selecting(),
                 ),
                 I::Prizes => pile_change(
                     &|l| l.prizes.len(),
-                    Selection::Prize { index: 0 },
-                    &LayoutSelectingState::default(),
+                    Selection::Prize { player, index: 0 },
+                    &self.selecting(),
                 ),
                 I::LostZone => pile_change(
                     &|l| l.lost_zone.len(),
-                    Selection::LostZone { index: 0 },
-                    &LayoutSelectingState::default(),
+                    Selection::LostZone { player, index: 0 },
+                    &self.selecting(),
                 ),
                 I::Stadium => pile_change(
                     &|l| l.stadium.len(),
-                    Selection::Stadium { index: 0 },
-                    &LayoutSelectingState::default(),
+                    Selection::Stadium { player, index: 0 },
+                    &self.selecting(),
                 ),
 
                 _ => leave_unchanged,
@@ -537,28 +733,97 @@ impl State
                 I::Select => InputMode::DeckSearch(st.add_to_selection()),
                 I::Move => InputMode::Move {
                     awaited_input: MoveAwaitedInput::Any,
+                    destination: player,
                     previous_state: PreviousMovingState::DeckSearch(st.clone()),
                 },
                 _ => leave_unchanged,
             },
-            InputMode::Look(_st) => {
-                todo!()
+            InputMode::Look { count, st } => {
+                let count = *count;
+                let deck_len = self.current_layout().deck.len();
+                // Changing how many to look at starts the choosing over
+                let look_at = |new_count: usize| InputMode::Look {
+                    count: new_count.clamp(1, deck_len.max(1)),
+                    st: Default::default(),
+                };
+                match input {
+                    I::Cancel => InputMode::Deck,
+                    I::Slot(u) => look_at(*u),
+                    I::Increment => look_at(count + 1),
+                    I::Decrement => look_at(count - 1),
+                    I::Left => {
+                        let new_highlight = st.current_highlight.saturating_sub(1);
+                        InputMode::Look { count, st: st.change_highlight(new_highlight) }
+                    },
+                    I::Right => {
+                        let new_highlight = (st.current_highlight + 1).min(count - 1);
+                        InputMode::Look { count, st: st.change_highlight(new_highlight) }
+                    },
+                    I::Select => InputMode::Look { count, st: st.add_to_selection() },
+                    I::Move => InputMode::Move {
+                        awaited_input: MoveAwaitedInput::Any,
+                        destination: player,
+                        previous_state: PreviousMovingState::Look { count, st: st.clone() },
+                    },
+                    // Reordering: Top puts the highlighted card on top of the
+                    // ones being looked at, Bottom puts it under the deck and
+                    // out of view
+                    I::Top | I::Bottom => {
+                        let highlight = st.current_highlight;
+                        let layout = self.current_layout_mut();
+                        let from_index = layout.deck.len() - 1 - highlight;
+                        let card = layout.deck.remove(from_index);
+                        let to_index = if let I::Top = input {
+                            layout.deck.push(card);
+                            layout.deck.len() - 1
+                        } else {
+                            layout.deck.insert(0, card);
+                            0
+                        };
+                        events.push(GameEvent::CardMoved {
+                            player,
+                            card: Some(card),
+                            from: Zone::Deck,
+                            from_index,
+                            to_player: player,
+                            to: Zone::Deck,
+                            to_index,
+                        });
+                        let count = if let I::Top = input { count } else { count - 1 };
+                        if count == 0 {
+                            InputMode::Deck
+                        } else {
+                            let new_highlight = highlight.min(count - 1);
+                            let st = PileSelectingState::default().change_highlight(new_highlight);
+                            InputMode::Look { count, st }
+                        }
+                    },
+                    _ => leave_unchanged,
+                }
+            },
+            InputMode::Observe => match input {
+                I::Cancel | I::Observe => InputMode::Deck,
+                _ => leave_unchanged,
             },
-            InputMode::Move { awaited_input, previous_state } => {
+            InputMode::Move { awaited_input, destination, previous_state } => {
                 // Dont 4get that removing from lists makes scawy index problems :3
                 // Could be worth it to just make a new list for everything that gets
                 // moved to avoid this problem since performance isnt rly
                 // that affected by this i think
-                self.handle_move(awaited_input.clone(), previous_state.clone(), input)
+                self.handle_move(
+                    awaited_input.clone(),
+                    *destination,
+                    previous_state.clone(),
+                    input,
+                    &mut events,
+                )
             },
 
-            InputMode::Swap { first_slot } => match input {
-                I::Cancel => {
-                    let selecting_state = Default::default();
-                    InputMode::Selecting(selecting_state)
-                },
+            InputMode::Swap { player: swap_player, first_slot } => match input {
+                I::Cancel => InputMode::Selecting(self.selecting()),
                 I::Slot(slot) => {
-                    if *slot == 0 || *slot > self.current_layout().slots.len() {
+                    let swap_player = *swap_player;
+                    if *slot == 0 || *slot > self.layout(swap_player).slots.len() {
                         leave_unchanged
                     }
                     else {
@@ -566,28 +831,87 @@ impl State
                             let first_slot = *first_slot - 1;
                             let second_slot = *slot - 1;
 
-                            let mut slots = &mut self.current_layout_mut().slots;
+                            let slots = &mut self.layout_mut(swap_player).slots;
                             slots.swap(first_slot, second_slot);
+                            events.push(GameEvent::SlotsSwapped {
+                                player: swap_player,
+                                first: first_slot,
+                                second: second_slot,
+                            });
 
-                            let selecting_state = Default::default();
-                            InputMode::Selecting(selecting_state)
+                            InputMode::Selecting(self.selecting())
                         }
                         else {
-                            InputMode::Swap { first_slot: Some(*slot) }
+                            InputMode::Swap { player: swap_player, first_slot: Some(*slot) }
                         }
                     }
                 },
                 _ => leave_unchanged,
             },
+            // Handled by update_chat
+            InputMode::Chat { .. } => leave_unchanged,
         };
         self.input_mode = next_input_mode;
+        events
+    }
+
+    /// `update` as the player whose side isn't being viewed, in their own
+    /// `mode`, for network games where each player has one.
+    pub fn update_as_other(&mut self, mode: &mut InputMode, input: &Input) -> Vec<GameEvent>
+    {
+        let viewing = self.currently_viewing;
+        std::mem::swap(&mut self.input_mode, mode);
+        self.currently_viewing = viewing.other();
+        let events = self.update(input);
+        std::mem::swap(&mut self.input_mode, mode);
+        self.currently_viewing = viewing;
+        events
+    }
+
+    /// Chatting works the same from every mode. None if `input` isn't to do
+    /// with chat.
+    fn update_chat(&mut self, input: &Input) -> Option<Vec<GameEvent>>
+    {
+        use Input as I;
+        let player = self.currently_viewing;
+        let mut events = Vec::new();
+        match (&mut self.input_mode, input) {
+            (InputMode::Chat { typed, .. }, I::Type(text)) => {
+                let room = MAX_CHAT_LENGTH.saturating_sub(typed.chars().count());
+                typed.extend(text.chars().take(room));
+            },
+            (InputMode::Chat { typed, .. }, I::Backspace) => {
+                typed.pop();
+            },
+            (InputMode::Chat { typed, previous }, I::Select | I::Cancel) => {
+                let text = typed.trim().to_string();
+                if *input == I::Select && !text.is_empty() {
+                    events.push(GameEvent::Chat { player, text });
+                }
+                self.input_mode = *previous.clone();
+            },
+            // Nothing else does anything while typing
+            (InputMode::Chat { .. }, _) => (),
+            (_, I::Chat) => {
+                let previous = Box::new(self.input_mode.clone());
+                self.input_mode = InputMode::Chat { typed: String::new(), previous };
+            },
+            (_, I::Emote(emote)) => {
+                let text = EMOTES.get(*emote)?.to_string();
+                events.push(GameEvent::Chat { player, text });
+            },
+            _ => return None,
+        }
+        Some(events)
     }
 
     fn handle_move(
         &mut self,
         awaited_input: MoveAwaitedInput,
+        to_player: Player,
         previous_state: PreviousMovingState,
         input: &Input,
+        events: &mut Vec<GameEvent>,
     ) -> InputMode
     {
         use Input as I;
@@ -595,21 +919,32 @@ impl State
             return match previous_state {
                 PreviousMovingState::Selecting(st) => InputMode::Selecting(st),
                 PreviousMovingState::DeckSearch(st) => InputMode::DeckSearch(st),
-                PreviousMovingState::Look(st) => InputMode::Look(st),
+                PreviousMovingState::Look { count, st } => InputMode::Look { count, st },
             };
         }
 
         let leave_unchanged = InputMode::Move {
             awaited_input: awaited_input.clone(),
+            destination: to_player,
             previous_state: previous_state.clone(),
         };
 
+        // Slots are numbered per side, so a chosen slot doesn't carry over
+        if let I::OtherBoard = input {
+            return InputMode::Move {
+                awaited_input: MoveAwaitedInput::Any,
+                destination: to_player.other(),
+                previous_state,
+            };
+        }
+
         if let I::Slot(slot) = input {
-            if *slot == 0 || *slot > self.current_layout().slots.len() {
+            if *slot == 0 || *slot > self.layout(to_player).slots.len() {
                 return leave_unchanged;
             }
             return InputMode::Move {
                 awaited_input: MoveAwaitedInput::SlotSpecific { slot: *slot - 1 },
+                destination: to_player,
                 previous_state,
             };
         }
@@ -626,9 +961,11 @@ impl State
             }
         }
 
-        let layout = self.current_layout_mut();
+        let player = self.currently_viewing;
 
         let mut moving_cards = Vec::new();
+        // Where each of moving_cards came from
+        let mut sources = Vec::new();
         let output = match previous_state {
             PreviousMovingState::Selecting(mut st) => {
                 st.selected.insert(st.current_highlight);
@@ -636,38 +973,81 @@ impl State
                 // Need to do removal in reverse to avoid index issues
                 selections_to_move.sort();
                 while let Some(selection) = selections_to_move.pop() {
+                    let from_player = selection.player();
+                    let layout = self.layout_mut(from_player);
                     let card_opt = match selection {
-                        Selection::Slot { slot_index, pokemon_index } => {
-                            pokemon_index.map(|pi| layout.slots[slot_index].cards.remove(pi))
+                        Selection::Slot { slot_index, pokemon_index, .. } => {
+                            pokemon_index.map(|pi| {
+                                let card = layout.slots[slot_index].cards.remove(pi);
+                                (card, Zone::Slot(slot_index), pi)
+                            })
+                        },
+                        Selection::Hand { index, .. } => {
+                            Some((layout.hand.remove(index), Zone::Hand, index))
+                        },
+                        Selection::Prize { index, .. } => {
+                            Some((layout.prizes.remove(index).card, Zone::Prizes, index))
+                        },
+                        Selection::Discard { index, .. } => {
+                            Some((layout.discard.remove(index), Zone::Discard, index))
+                        },
+                        Selection::LostZone { index, .. } => {
+                            Some((layout.lost_zone.remove(index), Zone::LostZone, index))
+                        },
+                        Selection::Stadium { index, .. } => {
+                            Some((layout.stadium.remove(index), Zone::Stadium, index))
                         },
-                        Selection::Hand { index } => Some(layout.hand.remove(index)),
-                        Selection::Prize { index } => Some(layout.prizes.remove(index).card),
-                        Selection::Discard { index } => Some(layout.discard.remove(index)),
-                        Selection::LostZone { index } => Some(layout.lost_zone.remove(index)),
-                        Selection::Stadium { index } => Some(layout.stadium.remove(index)),
                     };
-                    if let Some(card) = card_opt {
+                    if let Some((card, zone, index)) = card_opt {
                         moving_cards.push(card);
+                        sources.push((from_player, zone, index));
                     }
                 }
-                InputMode::Selecting(Default::default())
+                InputMode::Selecting(self.selecting())
             },
             PreviousMovingState::DeckSearch(mut st) => {
+                let layout = self.current_layout_mut();
                 st.selected.insert(st.current_highlight);
                 let mut indices_to_move: Vec<usize> = st.selected.into_iter().collect();
                 // Need to do removal in reverse to avoid index issues
                 indices_to_move.sort();
                 while let Some(index) = indices_to_move.pop() {
                     moving_cards.push(layout.deck.remove(index));
+                    sources.push((player, Zone::Deck, index));
                 }
                 InputMode::DeckSearch(Default::default())
             },
-            PreviousMovingState::Look(_) => {
-                todo!()
+            PreviousMovingState::Look { count, mut st } => {
+                let layout = self.current_layout_mut();
+                st.selected.insert(st.current_highlight);
+                let top = layout.deck.len() - 1;
+                let mut indices_to_move: Vec<usize> =
+                    st.selected.into_iter().map(|i| top - i).collect();
+                indices_to_move.sort();
+                let moved = indices_to_move.len();
+                while let Some(index) = indices_to_move.pop() {
+                    moving_cards.push(layout.deck.remove(index));
+                    sources.push((player, Zone::Deck, index));
+                }
+                // They came off top first, so they'd go back on top the other
+                // way up
+                if matches!(input, I::Top) {
+                    moving_cards.reverse();
+                    sources.reverse();
+                }
+                // Cards put back on top are still being looked at
+                let back_on_top = matches!(input, I::Top) && to_player == player;
+                let count = if back_on_top { count } else { count - moved };
+                if count == 0 {
+                    InputMode::Deck
+                } else {
+                    InputMode::Look { count, st: Default::default() }
+                }
             },
         };
 
-        let (destination, should_prepend): (&mut Pile, bool) =
+        let layout = self.layout_mut(to_player);
+        let (destination, to, should_prepend): (&mut Pile, Zone, bool) =
             if let MoveAwaitedInput::SlotSpecific { slot } = awaited_input {
                 let should_prepend = match input {
                     I::Append => false,
@@ -675,19 +1055,36 @@ impl State
                     _ => unreachable!(),
                 };
                 let destination = &mut layout.slots[slot].cards;
-                (destination, should_prepend)
+                (destination, Zone::Slot(slot), should_prepend)
             } else {
                 match input {
-                    I::Top => (&mut layout.deck, false),
-                    I::Bottom => (&mut layout.deck, true),
-                    I::Hand => (&mut layout.hand, false),
-                    I::Discard => (&mut layout.discard, false),
-                    I::LostZone => (&mut layout.lost_zone, false),
-                    I::Stadium => (&mut layout.stadium, false),
+                    I::Top => (&mut layout.deck, Zone::Deck, false),
+                    I::Bottom => (&mut layout.deck, Zone::Deck, true),
+                    I::Hand => (&mut layout.hand, Zone::Hand, false),
+                    I::Discard => (&mut layout.discard, Zone::Discard, false),
+                    I::LostZone => (&mut layout.lost_zone, Zone::LostZone, false),
+                    I::Stadium => (&mut layout.stadium, Zone::Stadium, false),
                     _ => unreachable!(),
                 }
             };
 
+        // Prepending puts each card in front of the last, so the order flips
+        let moved = moving_cards.len();
+        let first_index = destination.len();
+        for (i, (card, source)) in moving_cards.iter().zip(sources).enumerate() {
+            let (from_player, from, from_index) = source;
+            let to_index = if should_prepend { moved - 1 - i } else { first_index + i };
+            events.push(GameEvent::CardMoved {
+                player: from_player,
+                card: Some(*card),
+                from,
+                from_index,
+                to_player,
+                to,
+                to_index,
+            });
+        }
+
         if should_prepend {
             for card in moving_cards.into_iter() {
                 destination.insert(0, card)
@@ -698,3 +1095,157 @@ impl State
         output
     }
 }
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    /// Player 1's deck is 1 to 5, with 5 on top.
+    fn game() -> State
+    {
+        State::make(vec![1, 2, 3, 4, 5], vec![11, 12, 13], 0)
+    }
+
+    /// Everything that happened, for all of `inputs`.
+    fn play(st: &mut State, inputs: &[Input]) -> Vec<GameEvent>
+    {
+        inputs.iter().flat_map(|input| st.update(input)).collect()
+    }
+
+    /// How many cards are being looked at, if any.
+    fn looking_at(st: &State) -> Option<usize>
+    {
+        match st.input_mode {
+            InputMode::Look { count, .. } => Some(count),
+            _ => None,
+        }
+    }
+
+    /// Player 1 moving `card` within their deck.
+    fn moved_in_deck(from_index: usize, card: Card, to_index: usize) -> GameEvent
+    {
+        GameEvent::CardMoved {
+            player: Player::Player1,
+            card: Some(card),
+            from: Zone::Deck,
+            from_index,
+            to_player: Player::Player1,
+            to: Zone::Deck,
+            to_index,
+        }
+    }
+
+    #[test]
+    fn looking_at_the_top_cards_and_reordering_them()
+    {
+        use Input as I;
+        let mut st = game();
+        play(&mut st, &[I::Deck, I::Look, I::Increment, I::Increment]);
+        assert_eq!(looking_at(&st), Some(3));
+
+        // 4 goes under the deck and out of view
+        let events = play(&mut st, &[I::Right, I::Bottom]);
+        assert_eq!(events, vec![moved_in_deck(3, 4, 0)]);
+        assert_eq!(st.player1_layout.deck, vec![4, 1, 2, 3, 5]);
+        assert_eq!(looking_at(&st), Some(2));
+
+        // The highlight stays on the second card, now 3, which goes on top
+        let events = play(&mut st, &[I::Top]);
+        assert_eq!(events, vec![moved_in_deck(3, 3, 4)]);
+        assert_eq!(st.player1_layout.deck, vec![4, 1, 2, 5, 3]);
+        assert_eq!(looking_at(&st), Some(2));
+    }
+
+    #[test]
+    fn moving_looked_at_cards_back_on_top_keeps_their_order()
+    {
+        use Input as I;
+        let mut st = game();
+        play(&mut st, &[I::Deck, I::Look, I::Slot(3), I::Select, I::Right, I::Move, I::Top]);
+        assert_eq!(st.player1_layout.deck, vec![1, 2, 3, 4, 5]);
+        assert_eq!(looking_at(&st), Some(3));
+    }
+
+    #[test]
+    fn moving_looked_at_cards_elsewhere_stops_looking_at_them()
+    {
+        use Input as I;
+        let mut st = game();
+        play(&mut st, &[I::Deck, I::Look, I::Slot(3), I::Right, I::Move, I::Hand]);
+        assert_eq!(st.player1_layout.hand, vec![4]);
+        assert_eq!(st.player1_layout.deck, vec![1, 2, 3, 5]);
+        assert_eq!(looking_at(&st), Some(2));
+
+        play(&mut st, &[I::Select, I::Right, I::Move, I::Discard]);
+        assert_eq!(st.player1_layout.discard, vec![5, 3]);
+        assert_eq!(st.player1_layout.deck, vec![1, 2]);
+        assert!(matches!(st.input_mode, InputMode::Deck));
+    }
+
+    #[test]
+    fn looking_at_fewer_than_one_card_does_nothing()
+    {
+        use Input as I;
+        let mut st = game();
+        play(&mut st, &[I::Deck, I::Look, I::Decrement, I::Slot(0)]);
+        assert_eq!(looking_at(&st), Some(1));
+        play(&mut st, &[I::Slot(9)]);
+        assert_eq!(looking_at(&st), Some(5));
+    }
+
+    #[test]
+    fn moving_cards_from_both_sides_of_the_board()
+    {
+        use Input as I;
+        let mut st = game();
+        st.player1_layout.hand = vec![20, 21];
+        st.player2_layout.discard = vec![30];
+
+        // Picks 20 from player 1's hand and 30 from player 2's discard, then
+        // turns the move from player 2's side back to player 1's hand
+        let inputs =
+            [I::Hand, I::Select, I::OtherBoard, I::Discard, I::Move, I::OtherBoard, I::Hand];
+        let events = play(&mut st, &inputs);
+        assert_eq!(st.player1_layout.hand, vec![21, 30, 20]);
+        assert!(st.player2_layout.discard.is_empty());
+        assert_eq!(
+            events,
+            vec![
+                GameEvent::CardMoved {
+                    player: Player::Player2,
+                    card: Some(30),
+                    from: Zone::Discard,
+                    from_index: 0,
+                    to_player: Player::Player1,
+                    to: Zone::Hand,
+                    to_index: 1,
+                },
+                GameEvent::CardMoved {
+                    player: Player::Player1,
+                    card: Some(20),
+                    from: Zone::Hand,
+                    from_index: 0,
+                    to_player: Player::Player1,
+                    to: Zone::Hand,
+                    to_index: 2,
+                },
+            ]
+        );
+
+        // And the other way, onto player 2's side
+        let inputs = [I::Hand, I::Select, I::Right, I::Move, I::OtherBoard, I::Slot(2), I::Append];
+        let events = play(&mut st, &inputs);
+        assert_eq!(st.player1_layout.hand, vec![20]);
+        assert_eq!(st.player2_layout.slots[1].cards, vec![30, 21]);
+        let moved: Vec<_> = events
+            .iter()
+            .map(|event| match event {
+                GameEvent::CardMoved { player, to_player, to, .. } => (*player, *to_player, *to),
+                _ => panic!("{event:?}"),
+            })
+            .collect();
+        let to_slot = (Player::Player1, Player::Player2, Zone::Slot(1));
+        assert_eq!(moved, vec![to_slot, to_slot]);
+    }
+}
@@ -0,0 +1,188 @@
+use sdl2::image::LoadTexture;
+use sdl2::render::{Texture, TextureCreator};
+use sdl2::video::WindowContext;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+
+use crate::config::CardLibrary;
+use crate::load_cards::CardIndexer;
+use crate::state::Card;
+
+/// Roughly 120 full size cards.
+pub const DEFAULT_TEXTURE_BUDGET_BYTES: usize = 256 * 1024 * 1024;
+
+/// Image files read by the pre-warming thread, or why they couldn't be.
+type PrewarmReceiver = Receiver<(Card, Result<Vec<u8>, String>)>;
+
+struct CacheEntry<'a>
+{
+    texture: Texture<'a>,
+    bytes: usize,
+    last_used: u64,
+}
+
+/// Card textures, loaded the first time they're drawn and evicted least
+/// recently used first once they take up more than the budget.
+pub struct TextureCache<'a>
+{
+    tex_creator: &'a TextureCreator<WindowContext>,
+    library: CardLibrary,
+    budget_bytes: usize,
+    used_bytes: usize,
+    // Bumped on every lookup, for LRU
+    clock: u64,
+    entries: HashMap<Card, CacheEntry<'a>>,
+    // So a missing image is only tried (and complained about) once
+    failed: HashMap<Card, String>,
+    // Image files read from disk by the pre-warming thread, waiting to become
+    // textures (which has to happen on this thread)
+    prewarmed: Option<PrewarmReceiver>,
+}
+
+impl<'a> TextureCache<'a>
+{
+    pub fn new(
+        tex_creator: &'a TextureCreator<WindowContext>,
+        library: &CardLibrary,
+        budget_bytes: usize,
+    ) -> Self
+    {
+        TextureCache {
+            tex_creator,
+            library: library.clone(),
+            budget_bytes,
+            used_bytes: 0,
+            clock: 0,
+            entries: HashMap::new(),
+            failed: HashMap::new(),
+            prewarmed: None,
+        }
+    }
+
+    /// The card's texture, loading it if needed. None if it has no image.
+    pub fn get(&mut self, card: Card, indexer: &CardIndexer) -> Option<&Texture<'a>>
+    {
+        self.clock += 1;
+        if !self.entries.contains_key(&card) {
+            if self.failed.contains_key(&card) {
+                return None;
+            }
+            let (set, card_id) = indexer.identity(card);
+            let path = self.library.image_path(set, card_id);
+            match self.tex_creator.load_texture(&path) {
+                Ok(texture) => self.insert(card, texture),
+                Err(e) => {
+                    eprintln!("Couldn't load {}: {}", path.display(), e);
+                    self.failed.insert(card, e);
+                    return None;
+                },
+            }
+        }
+        let entry = self.entries.get_mut(&card)?;
+        entry.last_used = self.clock;
+        Some(&entry.texture)
+    }
+
+    pub fn is_missing(&self, card: Card) -> bool
+    {
+        self.failed.contains_key(&card)
+    }
+
+    /// Starts reading the cards' images in the background. Call `pump` to
+    /// turn them into textures as they arrive.
+    pub fn prewarm(&mut self, cards: &[Card], indexer: &CardIndexer)
+    {
+        let paths: Vec<(Card, PathBuf)> = cards
+            .iter()
+            .filter(|card| !self.entries.contains_key(card) && !self.failed.contains_key(card))
+            .map(|card| {
+                let (set, card_id) = indexer.identity(*card);
+                (*card, self.library.image_path(set, card_id))
+            })
+            .collect();
+
+        let (sender, receiver) = channel();
+        thread::spawn(move || {
+            for (card, path) in paths {
+                let bytes = fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e));
+                if sender.send((card, bytes)).is_err() {
+                    // Cache was dropped or started pre-warming something else
+                    return;
+                }
+            }
+        });
+        self.prewarmed = Some(receiver);
+    }
+
+    /// Uploads whatever the pre-warming thread has read so far. Pre-warming
+    /// stops once the cache is full rather than evicting cards in use.
+    pub fn pump(&mut self)
+    {
+        let Some(receiver) = &self.prewarmed else {
+            return;
+        };
+        let mut arrived = Vec::new();
+        let mut finished = false;
+        loop {
+            match receiver.try_recv() {
+                Ok(loaded) => arrived.push(loaded),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    finished = true;
+                    break;
+                },
+            }
+        }
+
+        for (card, bytes) in arrived {
+            if self.used_bytes >= self.budget_bytes {
+                finished = true;
+                break;
+            }
+            if self.entries.contains_key(&card) {
+                continue;
+            }
+            match bytes.and_then(|bytes| self.tex_creator.load_texture_bytes(&bytes)) {
+                Ok(texture) => self.insert(card, texture),
+                Err(e) => {
+                    self.failed.insert(card, e);
+                },
+            }
+        }
+
+        if finished {
+            self.prewarmed = None;
+        }
+    }
+
+    fn insert(&mut self, card: Card, texture: Texture<'a>)
+    {
+        let query = texture.query();
+        // 4 bytes a pixel is close enough whatever the actual format is
+        let bytes = query.width as usize * query.height as usize * 4;
+        self.used_bytes += bytes;
+        self.entries.insert(card, CacheEntry { texture, bytes, last_used: self.clock });
+        self.evict(card);
+    }
+
+    /// Drops least recently used textures until under budget, never `keep`.
+    fn evict(&mut self, keep: Card)
+    {
+        while self.used_bytes > self.budget_bytes {
+            let oldest = self
+                .entries
+                .iter()
+                .filter(|(card, _)| **card != keep)
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(card, _)| *card);
+            match oldest.and_then(|card| self.entries.remove(&card)) {
+                // Dropping the texture frees it
+                Some(entry) => self.used_bytes -= entry.bytes,
+                None => break,
+            }
+        }
+    }
+}
@@ -0,0 +1,435 @@
+//! Deck legality checks. Formats are described by TOML files in the card
+//! root's `formats/` directory, e.g. `formats/2009-modified.toml`:
+//!
+//! ```toml
+//! name = "2009 Modified"
+//! deck_size = 60
+//! max_copies = 4
+//! ace_spec_limit = 1
+//! radiant_limit = 1
+//! # Set codes or image directories. Leave out to allow every set
+//! legal_sets = ["DS", "HP", "DP", "MT", "SW", "GE", "MD", "LA", "SF", "PL", "RR", "SV", "AR"]
+//! ```
+//!
+//! Formats like Standard that rotate by regulation mark can list
+//! `legal_regulation_marks = ["G", "H"]` instead of (or as well as) sets.
+//!
+//! Checking card types needs metadata from the `CardDb`; cards without any
+//! are listed as unchecked rather than guessed at. Their copies are still
+//! limited, by card id, unless they're basic Energy going by their set
+//! (`energy`) or id (like `fire-energy`).
+
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{read_dir, read_to_string};
+
+use crate::card_db::{CardDb, CardInfo, Supertype};
+use crate::config::CardLibrary;
+use crate::deck_list::DeckList;
+use crate::load_cards::CardIndexer;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct FormatRules
+{
+    pub name: String,
+    pub deck_size: u32,
+    /// Per card name. Basic Energy is exempt.
+    pub max_copies: u32,
+    /// In the whole deck.
+    pub ace_spec_limit: u32,
+    /// Per card name.
+    pub prism_star_limit: u32,
+    /// In the whole deck.
+    pub radiant_limit: u32,
+    pub require_basic_pokemon: bool,
+    pub legal_sets: Option<Vec<String>>,
+    pub legal_regulation_marks: Option<Vec<String>>,
+}
+
+impl Default for FormatRules
+{
+    fn default() -> Self
+    {
+        FormatRules {
+            name: "Unlimited".to_string(),
+            deck_size: 60,
+            max_copies: 4,
+            ace_spec_limit: 1,
+            prism_star_limit: 1,
+            radiant_limit: 1,
+            require_basic_pokemon: true,
+            legal_sets: None,
+            legal_regulation_marks: None,
+        }
+    }
+}
+
+impl FormatRules
+{
+    /// Reads `formats/<format>.toml`.
+    pub fn load(library: &CardLibrary, format: &str) -> Result<Self, String>
+    {
+        let path = library.root.join("formats").join(format).with_extension("toml");
+        let contents =
+            read_to_string(&path).map_err(|e| format!("Problem reading {:?}: {}", path, e))?;
+        toml::from_str(&contents).map_err(|e| format!("Problem parsing {:?}: {}", path, e))
+    }
+
+    /// Names of every format in `formats/`, for `load`.
+    pub fn available(library: &CardLibrary) -> Vec<String>
+    {
+        let Ok(entries) = read_dir(library.root.join("formats")) else {
+            return Vec::new();
+        };
+        let mut formats: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().map(|ext| ext == "toml").unwrap_or(false))
+            .filter_map(|path| path.file_stem().and_then(|s| s.to_str()).map(|s| s.to_string()))
+            .collect();
+        formats.sort();
+        formats
+    }
+
+    fn is_legal_set(&self, set_dir: &str, info: Option<&CardInfo>) -> bool
+    {
+        let set_code = info.and_then(|info| info.set.as_deref());
+        let set_ok = match &self.legal_sets {
+            Some(legal_sets) => legal_sets.iter().any(|legal| {
+                legal.eq_ignore_ascii_case(set_dir)
+                    || set_code.map(|code| legal.eq_ignore_ascii_case(code)).unwrap_or(false)
+            }),
+            None => true,
+        };
+        // Cards without a mark (or metadata) only have their set checked
+        let mark = info.and_then(|info| info.regulation_mark.as_deref());
+        let mark_ok = match (&self.legal_regulation_marks, mark) {
+            (Some(legal_marks), Some(mark)) => {
+                legal_marks.iter().any(|legal| legal.eq_ignore_ascii_case(mark))
+            },
+            _ => true,
+        };
+        set_ok && mark_ok
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Violation
+{
+    WrongDeckSize
+    {
+        expected: u32, actual: u32
+    },
+    TooManyCopies
+    {
+        name: String, count: u32, max: u32
+    },
+    TooManyAceSpecs
+    {
+        count: u32, max: u32
+    },
+    TooManyPrismStar
+    {
+        name: String, count: u32, max: u32
+    },
+    TooManyRadiant
+    {
+        count: u32, max: u32
+    },
+    NoBasicPokemon,
+    IllegalSet
+    {
+        card: String, set: String
+    },
+}
+
+impl fmt::Display for Violation
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        use Violation as V;
+        match self {
+            V::WrongDeckSize { expected, actual } => {
+                write!(f, "deck has {} cards, should have {}", actual, expected)
+            },
+            V::TooManyCopies { name, count, max } => {
+                write!(f, "{} copies of {} (max {})", count, name, max)
+            },
+            V::TooManyAceSpecs { count, max } => {
+                write!(f, "{} ACE SPEC cards (max {})", count, max)
+            },
+            V::TooManyPrismStar { name, count, max } => {
+                write!(f, "{} copies of Prism Star card {} (max {})", count, name, max)
+            },
+            V::TooManyRadiant { count, max } => {
+                write!(f, "{} Radiant Pokémon (max {})", count, max)
+            },
+            V::NoBasicPokemon => write!(f, "no Basic Pokémon"),
+            V::IllegalSet { card, set } => write!(f, "{} is from {}, which isn't legal", card, set),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ValidationReport
+{
+    pub violations: Vec<Violation>,
+    /// Cards with no metadata, which type-based rules couldn't look at.
+    pub unchecked: Vec<String>,
+}
+
+impl ValidationReport
+{
+    pub fn is_legal(&self) -> bool
+    {
+        self.violations.is_empty()
+    }
+}
+
+fn is_basic_energy(info: &CardInfo) -> bool
+{
+    info.supertype == Supertype::Energy && info.has_subtype("Basic")
+}
+
+const BASIC_ENERGY_TYPES: [&str; 9] =
+    ["grass", "fire", "water", "lightning", "psychic", "fighting", "darkness", "metal", "fairy"];
+
+/// For cards without metadata: the `energy` set, or an id like
+/// `fire-energy` or `basic-fire-energy`.
+fn looks_like_basic_energy(set: &str, card: &str) -> bool
+{
+    let card = card.to_lowercase();
+    let card = card.strip_prefix("basic-").unwrap_or(&card);
+    set.eq_ignore_ascii_case("energy")
+        || BASIC_ENERGY_TYPES.iter().any(|kind| card.strip_suffix("-energy") == Some(kind))
+}
+
+pub fn validate(
+    deck: &DeckList,
+    rules: &FormatRules,
+    indexer: &CardIndexer,
+    db: &CardDb,
+) -> ValidationReport
+{
+    let mut report = ValidationReport::default();
+
+    let total = deck.total_cards();
+    if total != rules.deck_size {
+        let expected = rules.deck_size;
+        report.violations.push(Violation::WrongDeckSize { expected, actual: total });
+    }
+
+    // Copies are limited by name, so different prints of a card count together.
+    // Keeps first-seen order so the report follows the list. Each name has
+    // its count, its metadata if any and whether it's basic Energy
+    let mut counts_by_name: Vec<(String, u32, Option<&CardInfo>, bool)> = Vec::new();
+    let mut name_positions: HashMap<String, usize> = HashMap::new();
+    let mut ace_specs = 0;
+    let mut radiants = 0;
+    let mut has_basic_pokemon = false;
+
+    for entry in deck.entries.iter() {
+        let info = indexer.index_of(&entry.card).and_then(|card| db.get(card));
+        if info.is_none() && !report.unchecked.contains(&entry.card) {
+            report.unchecked.push(entry.card.clone());
+        }
+
+        if !rules.is_legal_set(&entry.set, info) {
+            report.violations.push(Violation::IllegalSet {
+                card: entry.card.clone(),
+                set: entry.set.clone(),
+            });
+        }
+
+        let name = match info {
+            Some(info) => info.name.clone(),
+            None => entry.card.clone(),
+        };
+        match name_positions.get(&name) {
+            Some(&i) => counts_by_name[i].1 += entry.count,
+            None => {
+                let basic_energy = match info {
+                    Some(info) => is_basic_energy(info),
+                    None => looks_like_basic_energy(&entry.set, &entry.card),
+                };
+                name_positions.insert(name.clone(), counts_by_name.len());
+                counts_by_name.push((name, entry.count, info, basic_energy));
+            },
+        }
+
+        if let Some(info) = info {
+            if info.has_subtype("ACE SPEC") {
+                ace_specs += entry.count;
+            }
+            if info.has_subtype("Radiant") {
+                radiants += entry.count;
+            }
+            if info.is_basic_pokemon() {
+                has_basic_pokemon = true;
+            }
+        }
+    }
+
+    for (name, count, info, basic_energy) in counts_by_name {
+        if basic_energy {
+            continue;
+        }
+        let is_prism_star = info.is_some_and(|info| info.has_subtype("Prism Star"));
+        if is_prism_star && count > rules.prism_star_limit {
+            report.violations.push(Violation::TooManyPrismStar {
+                name,
+                count,
+                max: rules.prism_star_limit,
+            });
+        } else if count > rules.max_copies {
+            report.violations.push(Violation::TooManyCopies { name, count, max: rules.max_copies });
+        }
+    }
+
+    if ace_specs > rules.ace_spec_limit {
+        report.violations.push(Violation::TooManyAceSpecs {
+            count: ace_specs,
+            max: rules.ace_spec_limit,
+        });
+    }
+    if radiants > rules.radiant_limit {
+        report.violations.push(Violation::TooManyRadiant {
+            count: radiants,
+            max: rules.radiant_limit,
+        });
+    }
+    // Without metadata for every card we can't tell there isn't a Basic
+    if rules.require_basic_pokemon && !has_basic_pokemon && report.unchecked.is_empty() {
+        report.violations.push(Violation::NoBasicPokemon);
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    /// Checks `list` against `rules`, with metadata for the card ids given.
+    fn check_with(list: &str, infos: &[(&str, &str)], rules: &FormatRules) -> ValidationReport
+    {
+        let deck = DeckList::parse(list).unwrap();
+        let indexer = CardIndexer::make(&[&deck]);
+        let mut db = CardDb::default();
+        for (card, json) in infos {
+            db.insert(indexer.index_of(card).unwrap(), serde_json::from_str(json).unwrap());
+        }
+        validate(&deck, rules, &indexer, &db)
+    }
+
+    /// Checks `list` with the default rules, sized to fit it.
+    fn check(list: &str, infos: &[(&str, &str)]) -> ValidationReport
+    {
+        let total = DeckList::parse(list).unwrap().total_cards();
+        check_with(list, infos, &FormatRules { deck_size: total, ..FormatRules::default() })
+    }
+
+    const RARE_CANDY: &str = r#"{"name": "Rare Candy", "supertype": "Trainer"}"#;
+    const FIRE_ENERGY: &str =
+        r#"{"name": "Fire Energy", "supertype": "Energy", "subtypes": ["Basic"]}"#;
+    const PACHIRISU: &str = r#"{"name": "Pachirisu", "supertype": "Pokémon", "stage": "Basic"}"#;
+
+    #[test]
+    fn cards_without_metadata_are_still_limited_by_id()
+    {
+        let report = check("10\npk\nrare-candy-pk-89\n", &[]);
+        assert_eq!(report.unchecked, vec!["rare-candy-pk-89".to_string()]);
+        assert_eq!(
+            report.violations,
+            vec![Violation::TooManyCopies {
+                name: "rare-candy-pk-89".to_string(),
+                count: 10,
+                max: 4
+            }]
+        );
+    }
+
+    #[test]
+    fn basic_energy_has_no_limit_with_or_without_metadata()
+    {
+        let list = "12\nenergy\ngrass-energy\n10\nds\nbasic-water-energy\n8\nhp\nhp-fire-101\n";
+        let report = check(list, &[("hp-fire-101", FIRE_ENERGY)]);
+        assert!(report.is_legal(), "{:?}", report.violations);
+
+        let report = check("5\nds\ndarkness-energy-special-ds-103\n", &[]);
+        assert_eq!(report.violations.len(), 1);
+    }
+
+    #[test]
+    fn prints_of_a_card_count_together()
+    {
+        let list = "3\npk\nrare-candy-pk-89\n2\nsw\nrare-candy-sw-88\n1\nmt\npachirisu-mt-86\n";
+        let report = check(
+            list,
+            &[
+                ("rare-candy-pk-89", RARE_CANDY),
+                ("rare-candy-sw-88", RARE_CANDY),
+                ("pachirisu-mt-86", PACHIRISU),
+            ],
+        );
+        assert_eq!(
+            report.violations,
+            vec![Violation::TooManyCopies { name: "Rare Candy".to_string(), count: 5, max: 4 }]
+        );
+    }
+
+    #[test]
+    fn prism_star_and_ace_spec_limits()
+    {
+        let cyrus = r#"{"name": "Cyrus", "supertype": "Trainer", "subtypes": ["Prism Star"]}"#;
+        let tool =
+            r#"{"name": "Scoop Up Cyclone", "supertype": "Trainer", "subtypes": ["ACE SPEC"]}"#;
+        let list = "2\nul\ncyrus-ul-120\n1\npl\nscoop-up-cyclone-pl-128\n\
+                    1\nbw\nscoop-up-cyclone-bw-1\n1\nmt\npachirisu-mt-86\n";
+        let report = check(
+            list,
+            &[
+                ("cyrus-ul-120", cyrus),
+                ("scoop-up-cyclone-pl-128", tool),
+                ("scoop-up-cyclone-bw-1", tool),
+                ("pachirisu-mt-86", PACHIRISU),
+            ],
+        );
+        assert_eq!(
+            report.violations,
+            vec![
+                Violation::TooManyPrismStar { name: "Cyrus".to_string(), count: 2, max: 1 },
+                Violation::TooManyAceSpecs { count: 2, max: 1 },
+            ]
+        );
+    }
+
+    #[test]
+    fn deck_size_sets_and_basic_pokemon()
+    {
+        let rules =
+            FormatRules { legal_sets: Some(vec!["MT".to_string()]), ..FormatRules::default() };
+        let list = "4\npk\nrare-candy-pk-89\n";
+        let report = check_with(list, &[("rare-candy-pk-89", RARE_CANDY)], &rules);
+        assert_eq!(
+            report.violations,
+            vec![
+                Violation::WrongDeckSize { expected: 60, actual: 4 },
+                Violation::IllegalSet {
+                    card: "rare-candy-pk-89".to_string(),
+                    set: "pk".to_string()
+                },
+                Violation::NoBasicPokemon,
+            ]
+        );
+
+        // Fine once there's a Basic, and not known to be wrong while a card is unchecked
+        let report = check("4\nmt\npachirisu-mt-86\n", &[("pachirisu-mt-86", PACHIRISU)]);
+        assert!(report.is_legal(), "{:?}", report.violations);
+        let report = check("4\nmt\npachirisu-mt-86\n", &[]);
+        assert!(report.is_legal(), "{:?}", report.violations);
+    }
+}
@@ -0,0 +1,282 @@
+//! What one player is allowed to see of a `State`. Drawing only ever gets a
+//! `PlayerView`, so nothing hidden from the player can end up on screen, and
+//! a view is all the other side of a network game is sent.
+//!
+//! Hidden from the viewer: the other player's hand, every face-down prize
+//! (their own too) and the order of both decks. The viewer's own deck is
+//! shown while they're searching it (in order, since cards are picked by
+//! position), looking at the top of it, or observing it (sorted, so the
+//! order stays hidden).
+//!
+//! Spectators can also get a neutral view, which hides both hands and
+//! decks, or an all-information view for coaching, which shows both hands
+//! and every prize.
+//!
+//! Events go through the same filter before they're shown in the log or
+//! sent: a card moving between places the viewer can't see isn't named.
+
+use serde::{Deserialize, Serialize};
+
+use crate::game_log::{GameEvent, Zone};
+use crate::state::{
+    Card, CardLayout, InputMode, Pile, Player, PokemonSlot, Selection, Slots, State, UIAlert,
+};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LayoutView
+{
+    pub slots: Slots<PokemonSlot>,
+    /// None for cards the viewer can't see.
+    pub hand: Vec<Option<Card>>,
+    pub discard: Pile,
+    pub deck_size: usize,
+    pub lost_zone: Pile,
+    /// None for face-down prizes.
+    pub prizes: Vec<Option<Card>>,
+    pub stadium: Pile,
+}
+
+/// What a view shows beyond the public parts of the board.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Reveal
+{
+    /// The viewer's hand and what they're looking at of their deck.
+    Viewer,
+    Nothing,
+    Everything,
+}
+
+impl LayoutView
+{
+    fn make(layout: &CardLayout, show_hand: bool, show_prizes: bool) -> Self
+    {
+        LayoutView {
+            slots: layout.slots.clone(),
+            hand: layout.hand.iter().map(|card| show_hand.then_some(*card)).collect(),
+            discard: layout.discard.clone(),
+            deck_size: layout.deck.len(),
+            lost_zone: layout.lost_zone.clone(),
+            prizes: layout
+                .prizes
+                .iter()
+                .map(|prize| (prize.is_face_up || show_prizes).then_some(prize.card))
+                .collect(),
+            stadium: layout.stadium.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlayerView
+{
+    pub viewer: Player,
+    pub player1: LayoutView,
+    pub player2: LayoutView,
+    /// As much of the viewer's deck as they're looking at, in the order it's
+    /// shown (see the module docs). Empty the rest of the time.
+    pub deck_shown: Pile,
+    pub input_mode: InputMode,
+    pub ui_alert: Option<UIAlert>,
+}
+
+impl PlayerView
+{
+    /// From the side `st` is viewing.
+    pub fn of(st: &State) -> Self
+    {
+        PlayerView::make(st, st.currently_viewing, &st.input_mode)
+    }
+
+    /// `input_mode` is the viewer's, which in a network game isn't
+    /// necessarily the state's (see `State::input_mode_of`).
+    pub fn make(st: &State, viewer: Player, input_mode: &InputMode) -> Self
+    {
+        PlayerView::project(st, viewer, input_mode, Reveal::Viewer)
+    }
+
+    /// For spectators: neither hand nor any of either deck, from the side
+    /// `st` is viewing.
+    pub fn neutral(st: &State) -> Self
+    {
+        PlayerView::project(st, st.currently_viewing, &st.input_mode, Reveal::Nothing)
+    }
+
+    /// For coaching: both hands and every prize, from the side `st` is
+    /// viewing.
+    pub fn all_info(st: &State) -> Self
+    {
+        PlayerView::project(st, st.currently_viewing, &st.input_mode, Reveal::Everything)
+    }
+
+    fn project(st: &State, viewer: Player, input_mode: &InputMode, reveal: Reveal) -> Self
+    {
+        let deck = &st.layout(viewer).deck;
+        let deck_shown = match input_mode {
+            _ if reveal == Reveal::Nothing => Vec::new(),
+            InputMode::DeckSearch(_) => deck.clone(),
+            // Top first
+            InputMode::Look { count, .. } => deck.iter().rev().take(*count).copied().collect(),
+            InputMode::Observe => {
+                let mut sorted = deck.clone();
+                sorted.sort();
+                sorted
+            },
+            _ => Vec::new(),
+        };
+        let shows_hand = |player| match reveal {
+            Reveal::Viewer => player == viewer,
+            Reveal::Nothing => false,
+            Reveal::Everything => true,
+        };
+        let show_prizes = reveal == Reveal::Everything;
+        PlayerView {
+            viewer,
+            player1: LayoutView::make(&st.player1_layout, shows_hand(Player::Player1), show_prizes),
+            player2: LayoutView::make(&st.player2_layout, shows_hand(Player::Player2), show_prizes),
+            deck_shown,
+            input_mode: input_mode.clone(),
+            ui_alert: st.ui_alert.clone(),
+        }
+    }
+
+    pub fn layout(&self, player: Player) -> &LayoutView
+    {
+        match player {
+            Player::Player1 => &self.player1,
+            Player::Player2 => &self.player2,
+        }
+    }
+
+    /// None for hidden cards and empty slots.
+    pub fn card_at(&self, selection: &Selection) -> Option<Card>
+    {
+        let layout = self.layout(selection.player());
+        match selection {
+            Selection::Hand { index, .. } => layout.hand[*index],
+            Selection::Slot { slot_index, pokemon_index, .. } => {
+                pokemon_index.map(|pi| layout.slots[*slot_index].cards[pi])
+            },
+            Selection::Discard { index, .. } => Some(layout.discard[*index]),
+            Selection::Prize { index, .. } => layout.prizes[*index],
+            Selection::LostZone { index, .. } => Some(layout.lost_zone[*index]),
+            Selection::Stadium { index, .. } => Some(layout.stadium[*index]),
+        }
+    }
+}
+
+/// Whether `viewer` can see the cards in `owner`'s `zone`. Prizes count as
+/// hidden, since an event doesn't say whether one was face up.
+fn zone_is_visible(viewer: Player, owner: Player, zone: Zone) -> bool
+{
+    match zone {
+        Zone::Hand => owner == viewer,
+        Zone::Deck | Zone::Prizes => false,
+        Zone::Discard | Zone::LostZone | Zone::Stadium | Zone::Slot(_) => true,
+    }
+}
+
+/// `event` as `viewer` may see it: a card moved between places hidden from
+/// them isn't named.
+pub fn event_seen_by(viewer: Player, event: &GameEvent) -> GameEvent
+{
+    let mut seen = event.clone();
+    if let GameEvent::CardMoved { player, card, from, to_player, to, .. } = &mut seen {
+        if !zone_is_visible(viewer, *player, *from) && !zone_is_visible(viewer, *to_player, *to) {
+            *card = None;
+        }
+    }
+    seen
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::state::PrizeCard;
+
+    /// Player 1 has 20 and 21 in hand and prizes 22 (face down) and 23 (face
+    /// up), player 2 the same from 30, and player 1's deck is 3, 1, 2 with 2
+    /// on top.
+    fn board() -> State
+    {
+        let mut st = State::make(vec![3, 1, 2], vec![10, 11], 0);
+        for (layout, first) in [(&mut st.player1_layout, 20), (&mut st.player2_layout, 30)] {
+            layout.hand = vec![first, first + 1];
+            layout.prizes = vec![
+                PrizeCard { card: first + 2, is_face_up: false },
+                PrizeCard { card: first + 3, is_face_up: true },
+            ];
+        }
+        st
+    }
+
+    #[test]
+    fn make_hides_the_opponents_hand_and_face_down_prizes()
+    {
+        let view = PlayerView::make(&board(), Player::Player1, &InputMode::Deck);
+        assert_eq!(view.player1.hand, vec![Some(20), Some(21)]);
+        assert_eq!(view.player2.hand, vec![None, None]);
+        assert_eq!(view.player1.prizes, vec![None, Some(23)]);
+        assert_eq!(view.player2.prizes, vec![None, Some(33)]);
+        assert_eq!((view.player1.deck_size, view.player2.deck_size), (3, 2));
+        assert!(view.deck_shown.is_empty());
+    }
+
+    #[test]
+    fn make_shows_the_deck_only_while_looking_at_it()
+    {
+        let st = board();
+        let shown = |mode: InputMode| PlayerView::make(&st, Player::Player1, &mode).deck_shown;
+        assert_eq!(shown(InputMode::DeckSearch(Default::default())), vec![3, 1, 2]);
+        assert_eq!(shown(InputMode::Look { count: 2, st: Default::default() }), vec![2, 1]);
+        assert_eq!(shown(InputMode::Observe), vec![1, 2, 3]);
+        assert!(shown(InputMode::Deck).is_empty());
+    }
+
+    #[test]
+    fn neutral_hides_both_hands()
+    {
+        let mut st = board();
+        st.input_mode = InputMode::DeckSearch(Default::default());
+        let view = PlayerView::neutral(&st);
+        assert_eq!(view.player1.hand, vec![None, None]);
+        assert_eq!(view.player2.hand, vec![None, None]);
+        assert_eq!(view.player1.prizes, vec![None, Some(23)]);
+        assert!(view.deck_shown.is_empty());
+    }
+
+    #[test]
+    fn all_info_shows_both_hands_and_every_prize()
+    {
+        let view = PlayerView::all_info(&board());
+        assert_eq!(view.player1.hand, vec![Some(20), Some(21)]);
+        assert_eq!(view.player2.hand, vec![Some(30), Some(31)]);
+        assert_eq!(view.player1.prizes, vec![Some(22), Some(23)]);
+        assert_eq!(view.player2.prizes, vec![Some(32), Some(33)]);
+    }
+
+    #[test]
+    fn events_only_name_cards_the_viewer_could_see()
+    {
+        let moved = |from, to| GameEvent::CardMoved {
+            player: Player::Player1,
+            card: Some(2),
+            from,
+            from_index: 0,
+            to_player: Player::Player1,
+            to,
+            to_index: 0,
+        };
+        let card_seen = |viewer, event: &GameEvent| match event_seen_by(viewer, event) {
+            GameEvent::CardMoved { card, .. } => card,
+            _ => unreachable!(),
+        };
+        let drawn = moved(Zone::Deck, Zone::Hand);
+        assert_eq!(card_seen(Player::Player1, &drawn), Some(2));
+        assert_eq!(card_seen(Player::Player2, &drawn), None);
+        let discarded = moved(Zone::Hand, Zone::Discard);
+        assert_eq!(card_seen(Player::Player2, &discarded), Some(2));
+        let prized = moved(Zone::Deck, Zone::Prizes);
+        assert_eq!(card_seen(Player::Player1, &prized), None);
+    }
+}